@@ -4,6 +4,27 @@ use cc2538_pac::{soc_adc, Cctest, RfcoreXreg, SocAdc};
 
 use core::marker::ConstParamTy;
 
+use crate::dma;
+
+/// The uDMA channel that is requested by the ADC after a conversion of AIN0 in a sequence.
+/// A conversion of AINn requests channel `ADC_DMA_CHANNEL + n`.
+const ADC_DMA_CHANNEL: usize = 24;
+
+/// The maximum amount of samples in a half of the buffer of [`Adc::start_continuous`], which
+/// is the maximum transfer size of [`dma::Channel::set_transfer_size`].
+const MAX_HALF_LEN: usize = 1024;
+
+/// Errors of [`Adc::start_continuous`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcError {
+    /// Only the single ended inputs AIN0 to AIN7 trigger the uDMA.
+    UnsupportedChannel,
+    /// The uDMA channel is not the channel that is triggered by the input of the ADC.
+    WrongDmaChannel,
+    /// The buffer is empty, has an odd length or is longer than 2048 bytes.
+    InvalidBuffer,
+}
+
 /// The channel the ADC is using when calling [`Adc::get`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ConstParamTy)]
 pub enum AdcChannel {
@@ -24,6 +45,28 @@ pub enum AdcChannel {
     VddDiv3 = 0b1111,
 }
 
+impl AdcChannel {
+    /// Return `true` for the channels that convert the difference between two inputs.
+    pub const fn is_differential(&self) -> bool {
+        matches!(
+            self,
+            AdcChannel::Ain0Ain1
+                | AdcChannel::Ain2Ain3
+                | AdcChannel::Ain4Ain5
+                | AdcChannel::Ain6Ain7
+        )
+    }
+}
+
+/// A conversion result, right aligned to the resolution of the decimation rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcReading {
+    /// The result of a single ended channel. Results below ground are clamped to 0.
+    SingleEnded(u16),
+    /// The signed result of a differential channel.
+    Differential(i16),
+}
+
 /// The reference voltage used for the conversion in the ADC.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RefVoltage {
@@ -49,16 +92,41 @@ pub enum DecimationRate {
     Dec512 = 0b11,
 }
 
+impl DecimationRate {
+    /// Return the amount of bits of a result, including the sign bit.
+    pub const fn resolution(&self) -> u8 {
+        match self {
+            DecimationRate::Dec64 => 7,
+            DecimationRate::Dec128 => 9,
+            DecimationRate::Dec256 => 10,
+            DecimationRate::Dec512 => 12,
+        }
+    }
+}
+
 impl Default for DecimationRate {
     fn default() -> Self {
         Self::Dec512
     }
 }
 
+/// The event that starts a conversion of the ADC.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerSource {
+    /// A conversion is started by software when calling [`Adc::read`].
+    #[default]
+    Manual = 0b11,
+    /// Conversions are started back to back, without waiting for a trigger.
+    FullSpeed = 0b01,
+    /// A conversion is started by the timer 1 channel 0 compare event.
+    Timer = 0b10,
+}
+
 pub struct Adc<'p, const CHANNEL: AdcChannel> {
     channel: AdcChannel,
     reference: RefVoltage,
     rate: DecimationRate,
+    trigger: TriggerSource,
     _adc: PhantomData<&'p mut SocAdc>,
 }
 
@@ -74,6 +142,7 @@ impl<const CHANNEL: AdcChannel> Adc<'_, CHANNEL> {
             channel: CHANNEL,
             reference: Default::default(),
             rate: Default::default(),
+            trigger: Default::default(),
             _adc: PhantomData,
         }
     }
@@ -88,10 +157,200 @@ impl<const CHANNEL: AdcChannel> Adc<'_, CHANNEL> {
         self.rate = rate;
     }
 
+    /// Set the event that starts a conversion.
+    pub fn set_trigger_source(&mut self, trigger: TriggerSource) {
+        self.trigger = trigger;
+    }
+
     /// Get the ADC value.
+    ///
+    /// With [`TriggerSource::Manual`], a single conversion is started and its result is
+    /// returned. With the other trigger sources, a conversion sequence ending at the channel of
+    /// the ADC is configured and the result of the next conversion is returned. Note that for
+    /// the single ended inputs, the hardware converts all channels from AIN0 up to the channel.
     pub fn read(&self) -> u16 {
-        unsafe { Self::regs().adccon1().modify(|_, w| w.stsel().bits(0b11)) };
+        unsafe {
+            Self::regs()
+                .adccon1()
+                .modify(|_, w| w.stsel().bits(self.trigger as u8))
+        };
+
+        let saved = self.enable_temperature_sensor();
+
+        if self.trigger == TriggerSource::Manual {
+            // Writing the extra conversion register starts the conversion.
+            unsafe {
+                Self::regs().adccon3().write(|w| {
+                    w.ech()
+                        .bits(self.channel as u8)
+                        .ediv()
+                        .bits(self.rate as u8)
+                        .eref()
+                        .bits(self.reference as u8)
+                });
+            }
+        } else {
+            self.configure_sequence();
+        }
+
+        // Poll until end of conversion
+        // TODO(thvdveld): can we make this asynchronous?
+        while !self.end_of_conversion() {}
+
+        let res = Self::read_result();
+
+        self.restore_temperature_sensor(saved);
+
+        res
+    }
+
+    /// Get the mean of `samples` ADC values, after throwing away `discard` values.
+    ///
+    /// `discard + samples` conversions are done with [`Adc::read`]. The first conversions after
+    /// switching channels can be off while the input settles, which is why they can be
+    /// discarded. Averaging reduces the noise of a high impedance source. Like [`Adc::read`], the
+    /// mean is a left aligned two's complement value.
+    ///
+    /// # Panics
+    /// Panics when `samples` is 0.
+    pub fn read_averaged(&self, samples: u8, discard: u8) -> u16 {
+        assert!(samples > 0);
+
+        for _ in 0..discard {
+            let _ = self.read();
+        }
+
+        // The result is a two's complement value, so it is averaged as a signed value.
+        let sum: i32 = (0..samples).map(|_| self.read() as i16 as i32).sum();
+        (sum / samples as i32) as i16 as u16
+    }
+
+    /// Get the ADC value, right aligned and sign extended.
+    ///
+    /// The ADC result is a two's complement value with [`DecimationRate::resolution`] bits. For
+    /// the differential channels this is returned as a signed value, for the other channels
+    /// negative results (caused by an offset close to ground) are clamped to 0.
+    pub fn read_value(&self) -> AdcReading {
+        let shift = 16 - self.rate.resolution();
+        // The result is left aligned, an arithmetic shift extends the sign bit.
+        let value = (self.read() as i16) >> shift;
+
+        if self.channel.is_differential() {
+            AdcReading::Differential(value)
+        } else {
+            AdcReading::SingleEnded(value.max(0) as u16)
+        }
+    }
+
+    /// Start converting continuously, with the uDMA copying every result into the ring buffer
+    /// `buf`.
+    ///
+    /// Only the single ended inputs AIN0 to AIN7 can trigger the uDMA: AINn uses channel
+    /// `24 + n`, which must be the channel that is passed. With [`TriggerSource::Manual`], the
+    /// conversions are started at full speed, otherwise the selected trigger source is used.
+    ///
+    /// `buf` is used as two halves in ping-pong mode: while the uDMA fills one half, the other
+    /// half is read with [`AdcStream::filled`] and handed back with [`AdcStream::release`]. The
+    /// CPU is only involved once per half. A half holds at most 1024 samples, so `buf` must
+    /// have an even length of at most 2048 bytes.
+    ///
+    /// `ADCL` and `ADCH` are registers at separate word addresses and a conversion triggers a
+    /// single uDMA request, so only `ADCH` is copied: `buf` receives the 8 most significant bits
+    /// of every result. This is the complete result for [`DecimationRate::Dec64`] (7 bits), the
+    /// 9, 10 and 12 bit results of the other rates are truncated to 8 bits. Use
+    /// [`AdcStream::samples`] to get the results in the same format as [`Adc::read`].
+    ///
+    /// A conversion takes `(decimation rate + 16) * 0.25 us`, which limits the sustained sample
+    /// rate at full speed to 50 kHz for [`DecimationRate::Dec64`], 27.7 kHz for
+    /// [`DecimationRate::Dec128`], 14.7 kHz for [`DecimationRate::Dec256`] and 7.5 kHz for
+    /// [`DecimationRate::Dec512`]. When converting a sequence (AIN0 up to the channel of the
+    /// ADC), this rate is divided by the length of the sequence.
+    pub fn start_continuous<'a>(
+        &'a mut self,
+        mut dma_channel: dma::Channel,
+        buf: &'a mut [u8],
+    ) -> Result<AdcStream<'a>, AdcError> {
+        if (self.channel as usize) > AdcChannel::Ain7 as usize {
+            return Err(AdcError::UnsupportedChannel);
+        }
+
+        if dma_channel.number() != ADC_DMA_CHANNEL + self.channel as usize {
+            return Err(AdcError::WrongDmaChannel);
+        }
+
+        if buf.is_empty() || !buf.len().is_multiple_of(2) || buf.len() > 2 * MAX_HALF_LEN {
+            return Err(AdcError::InvalidBuffer);
+        }
+
+        // The primary control structure fills the first half, the alternate one the second.
+        dma_channel.use_alternate(false);
+        dma_channel.set_assignment(0);
+        let alternate = dma_channel.other_structure();
+        let mut halves = [dma_channel, alternate];
+        let half_len = buf.len() / 2;
+
+        for (i, half) in halves.iter_mut().enumerate() {
+            let end = buf.as_ptr() as u32 + ((i + 1) * half_len) as u32 - 1;
+            half.configure(|c| {
+                c.set_source_end_address(Self::regs().adch().as_ptr() as u32)
+                    .set_destination_end_address(end)
+                    .set_source_size(dma::DataSize::Data8bit)
+                    .set_destination_size(dma::DataSize::Data8bit)
+                    .set_source_increment(dma::AddressIncrement::None)
+                    .set_destination_increment(dma::AddressIncrement::Increment8bit)
+                    .set_arbitration_size(dma::Arbitration::Transfer1)
+                    .set_transfer_size((half_len - 1) as u16)
+                    .set_transfer_mode(dma::TransferMode::PingPong);
+            });
+        }
+
+        halves[0].allow_periph_requests(true);
+        halves[0].enable();
+
+        let trigger = match self.trigger {
+            TriggerSource::Manual => TriggerSource::FullSpeed,
+            trigger => trigger,
+        };
+
+        self.configure_sequence();
+        unsafe {
+            Self::regs()
+                .adccon1()
+                .modify(|_, w| w.stsel().bits(trigger as u8))
+        };
 
+        Ok(AdcStream {
+            halves,
+            buf,
+            next: 0,
+            rate: self.rate,
+        })
+    }
+
+    /// Configure the conversion sequence, which is started by the trigger source.
+    fn configure_sequence(&self) {
+        unsafe {
+            Self::regs().adccon2().write(|w| {
+                w.sch()
+                    .bits(self.channel as u8)
+                    .sdiv()
+                    .bits(self.rate as u8)
+                    .sref()
+                    .bits(self.reference as u8)
+            });
+        }
+    }
+
+    /// Read the result of the last conversion, left aligned in a `u16`.
+    fn read_result() -> u16 {
+        let mut res = Self::regs().adcl().read().bits() & 0xfc;
+        res |= Self::regs().adch().read().bits() << 8;
+        res as u16
+    }
+
+    /// Connect the temperature sensor to the ADC when it is the channel of the ADC.
+    /// Returns the values of the registers that are modified.
+    fn enable_temperature_sensor(&self) -> (u32, u32) {
         let mut cctest_tr0 = 0;
         let mut rfcore_xreg_atest = 0;
         if self.channel == AdcChannel::TemperatureSensor {
@@ -105,26 +364,11 @@ impl<const CHANNEL: AdcChannel> Adc<'_, CHANNEL> {
                     .modify(|_, w| w.atest_ctrl().bits(0x1));
             }
         }
-        unsafe {
-            Self::regs().adccon3().write(|w| {
-                w.ech()
-                    .bits(self.channel as u8)
-                    .ediv()
-                    .bits(self.rate as u8)
-                    .eref()
-                    .bits(self.reference as u8)
-            });
-        }
-
-        // Poll until end of conversion
-        // TODO(thvdveld): can we make this asynchronous?
-        while !self.end_of_conversion() {}
-
-        // Read conversion
-        let mut res = Self::regs().adcl().read().bits() & 0xfc;
-        res |= Self::regs().adch().read().bits() << 8;
+        (cctest_tr0, rfcore_xreg_atest)
+    }
 
-        // Restore radio and temperature sensor.
+    /// Restore radio and temperature sensor.
+    fn restore_temperature_sensor(&self, (cctest_tr0, rfcore_xreg_atest): (u32, u32)) {
         if self.channel == AdcChannel::TemperatureSensor {
             unsafe {
                 (*Cctest::ptr()).tr0().write(|w| w.bits(cctest_tr0));
@@ -133,7 +377,6 @@ impl<const CHANNEL: AdcChannel> Adc<'_, CHANNEL> {
                     .write(|w| w.bits(rfcore_xreg_atest));
             }
         }
-        res as u16
     }
 
     // Check if the conversion is finished.
@@ -142,10 +385,116 @@ impl<const CHANNEL: AdcChannel> Adc<'_, CHANNEL> {
     }
 }
 
+/// Results of the ADC that are being copied by the uDMA, see [`Adc::start_continuous`].
+pub struct AdcStream<'a> {
+    /// The primary and the alternate control structure of the uDMA channel.
+    halves: [dma::Channel; 2],
+    buf: &'a mut [u8],
+    /// The half that is filled first.
+    next: usize,
+    rate: DecimationRate,
+}
+
+impl AdcStream<'_> {
+    /// Return the half of the buffer that is filled next, when the uDMA finished it.
+    ///
+    /// Every byte is the `ADCH` register, see [`AdcStream::samples`] for the results in the
+    /// format of [`Adc::read`]. Hand the half back to the uDMA with [`AdcStream::release`]
+    /// before the uDMA finishes the other half, otherwise conversions are lost.
+    pub fn filled(&self) -> Option<&[u8]> {
+        if self.halves[self.next].get_mode() != dma::TransferMode::Stop {
+            return None;
+        }
+
+        let half_len = self.buf.len() / 2;
+        Some(&self.buf[self.next * half_len..][..half_len])
+    }
+
+    /// Return the results of the half returned by [`AdcStream::filled`], left aligned in a
+    /// `u16` like [`Adc::read`].
+    pub fn samples(&self) -> impl Iterator<Item = u16> + '_ {
+        // For 7 bit results, the lowest bit of ADCH is not part of the result.
+        let mask = match self.rate {
+            DecimationRate::Dec64 => 0xfe00,
+            _ => 0xff00,
+        };
+
+        self.filled()
+            .unwrap_or(&[])
+            .iter()
+            .map(move |b| ((*b as u16) << 8) & mask)
+    }
+
+    /// Hand the half returned by [`AdcStream::filled`] back to the uDMA.
+    ///
+    /// Does nothing when the half is not filled yet.
+    pub fn release(&mut self) {
+        let half = &mut self.halves[self.next];
+        if half.get_mode() != dma::TransferMode::Stop {
+            return;
+        }
+
+        // The control word still holds the configuration of the half, which restarts it.
+        half.set_transfer_mode(dma::TransferMode::PingPong);
+        self.next ^= 1;
+    }
+
+    /// Return `true` when both halves are filled, such that the uDMA stopped and conversions
+    /// are lost.
+    pub fn is_overrun(&self) -> bool {
+        self.halves
+            .iter()
+            .all(|half| half.get_mode() == dma::TransferMode::Stop)
+    }
+
+    /// Stop converting and return the uDMA channel.
+    pub fn stop(self) -> dma::Channel {
+        unsafe {
+            (*SocAdc::ptr())
+                .adccon1()
+                .modify(|_, w| w.stsel().bits(TriggerSource::Manual as u8))
+        };
+
+        let [primary, _] = self.halves;
+        primary.disable();
+        primary
+    }
+}
+
 impl Adc<'_, { AdcChannel::TemperatureSensor }> {
     /// Return a temperature value.
+    ///
+    /// The temperature sensor is connected to the ADC through the ATEST register of the radio,
+    /// which disturbs an ongoing reception or transmission. Use
+    /// [`Adc::try_get_converted_temperature`] when the radio may be in use.
     pub fn get_converted_temperature(&self) -> u32 {
         let val = self.read();
         25_000 + ((val as u32 >> 4) - 1_422) * 10_000 / 42
     }
+
+    /// Check if the temperature sensor can be sampled without disturbing the radio.
+    ///
+    /// This is the case when the receiver is disabled and the radio is not transmitting.
+    pub fn is_safe_to_sample() -> bool {
+        let xreg = unsafe { &*RfcoreXreg::ptr() };
+        let fsmstat1 = xreg.fsmstat1().read();
+
+        xreg.rxenable().read().rxenmask().bits() == 0
+            && fsmstat1.tx_active().bit_is_clear()
+            && fsmstat1.sfd().bit_is_clear()
+    }
+
+    /// Return a temperature value, or `None` when the radio is in use.
+    ///
+    /// The conversion is done in a critical section, such that the radio can not be enabled
+    /// by an interrupt handler during the conversion.
+    pub fn try_get_converted_temperature(&self) -> Option<u32> {
+        cortex_m::interrupt::free(|_| {
+            if Self::is_safe_to_sample() {
+                Some(self.get_converted_temperature())
+            } else {
+                None
+            }
+        })
+    }
 }
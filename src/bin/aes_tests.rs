@@ -43,11 +43,9 @@ fn inner_main() -> Result<(), &'static str> {
 
     let mut sys_ctrl = sys_ctrl.freeze();
 
-    sys_ctrl.reset_aes();
-    sys_ctrl.clear_reset_aes();
+    sys_ctrl.pulse_reset_aes();
 
-    sys_ctrl.reset_pka();
-    sys_ctrl.clear_reset_pka();
+    sys_ctrl.pulse_reset_pka();
 
     let mut aes_crypto = Crypto::new(&mut periph.aes, &mut periph.pka);
 
@@ -61,7 +59,9 @@ fn inner_main() -> Result<(), &'static str> {
         crate::aes_engine::keys::AesKeySize::Key128,
         0,
     );
-    aes_crypto.load_key(&aes_keys_128);
+    aes_crypto
+        .load_key(&aes_keys_128)
+        .map_err(|_| "loading the key failed")?;
 
     let adata: [u8; 0] = [];
     let mut mdata = [
@@ -78,21 +78,24 @@ fn inner_main() -> Result<(), &'static str> {
 
     let ccm_info = AesCcmInfo::new(0, 2, 0).with_added_auth_data(&adata[..]);
 
-    aes_crypto.ccm_encrypt(
-        &ccm_info,
-        &nonce[..],
-        &mdata[..],
-        &mut data_out[..],
-        &mut tag[..],
-    );
+    aes_crypto
+        .ccm_encrypt(
+            &ccm_info,
+            &nonce[..],
+            &mdata[..],
+            &mut data_out[..],
+            &mut tag[..],
+        )
+        .map_err(|_| "CCM encryption failed")?;
 
     rprintln!("{:0x?}", data_out);
 
-    aes_crypto.ccm_decrypt(&ccm_info, &nonce[..], &data_out[..], &mut mdata[..]);
+    aes_crypto
+        .ccm_decrypt(&ccm_info, &nonce[..], &data_out[..], &mut mdata[..])
+        .map_err(|_| "CCM decryption failed")?;
     rprintln!("{:0x?}", mdata);
 
-    sys_ctrl.reset_aes();
-    sys_ctrl.clear_reset_aes();
+    sys_ctrl.pulse_reset_aes();
 
     const P_3_TV: [u8; 10] = [0x2b, 0x48, 0x4c, 0xd5, 0x3d, 0x74, 0xf0, 0xa6, 0xed, 0x8b];
     let nonce = [
@@ -102,10 +105,77 @@ fn inner_main() -> Result<(), &'static str> {
     let mut tag = [0; 16];
 
     let ccm_info = AesCcmInfo::new(0, 2, 0).with_added_auth_data(&[]);
-    aes_crypto.ccm_encrypt(&ccm_info, &nonce, &P_3_TV, &mut data_out[..], &mut tag[..]);
+    aes_crypto
+        .ccm_encrypt(&ccm_info, &nonce, &P_3_TV, &mut data_out[..], &mut tag[..])
+        .map_err(|_| "CCM encryption failed")?;
 
     rprintln!("data out: {:0x?}", data_out);
 
+    // NIST SP 800-38C, example 3. The multi-part operation splits both the added
+    // authentication data and the payload on a block boundary, and must give the same
+    // ciphertext and tag as the one-shot operation.
+    let key = AesKey::Key128([
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e,
+        0x4f,
+    ]);
+    aes_crypto
+        .load_key(&AesKeys::create(&[key], AesKeySize::Key128, 0))
+        .map_err(|_| "loading the key failed")?;
+
+    let nonce = [
+        0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b,
+    ];
+    let mut adata = [0u8; 20];
+    adata.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+    let mut payload = [0u8; 24];
+    payload
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, b)| *b = 0x20 + i as u8);
+    const CCM_CIPHERTEXT: [u8; 24] = [
+        0xe3, 0xb2, 0x01, 0xa9, 0xf5, 0xb7, 0x1a, 0x7a, 0x9b, 0x1c, 0xea, 0xec, 0xcd, 0x97, 0xe7,
+        0x0b, 0x61, 0x76, 0xaa, 0xd9, 0xa4, 0x42, 0x8a, 0xa5,
+    ];
+    const CCM_TAG: [u8; 8] = [0x48, 0x43, 0x92, 0xfb, 0xc1, 0xb0, 0x99, 0x51];
+
+    let ccm_info = AesCcmInfo::new(0, 3, 8).with_added_auth_data(&adata);
+
+    let mut one_shot = [0u8; 24];
+    let mut one_shot_tag = [0u8; 16];
+    aes_crypto
+        .ccm_encrypt(
+            &ccm_info,
+            &nonce,
+            &payload,
+            &mut one_shot,
+            &mut one_shot_tag,
+        )
+        .map_err(|_| "CCM encryption failed")?;
+
+    if one_shot != CCM_CIPHERTEXT || one_shot_tag[..8] != CCM_TAG {
+        return Err("CCM encryption returned wrong data");
+    }
+
+    let mut multi_part = [0u8; 24];
+    let mut multi_part_tag = [0u8; 16];
+    let mut ccm = aes_crypto
+        .ccm_encrypt_start(&ccm_info, &nonce, adata.len(), payload.len())
+        .map_err(|_| "starting the CCM encryption failed")?;
+    ccm.update_adata(&adata[..16])
+        .and_then(|_| ccm.update_adata(&adata[16..]))
+        .map_err(|_| "feeding the added authentication data failed")?;
+    ccm.update(&payload[..16], &mut multi_part[..16])
+        .and_then(|_| ccm.update(&payload[16..], &mut multi_part[16..]))
+        .map_err(|_| "feeding the payload failed")?;
+    ccm.finish(&mut multi_part_tag)
+        .map_err(|_| "finishing the CCM encryption failed")?;
+
+    if multi_part != one_shot || multi_part_tag != one_shot_tag {
+        return Err("multi-part CCM encryption differs from the one-shot encryption");
+    }
+
+    rprintln!("Multi-part CCM: {:0x?}", multi_part_tag);
+
     let key128 = AesKey::Key128([
         0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
         0x3c,
@@ -113,7 +183,8 @@ fn inner_main() -> Result<(), &'static str> {
     let aes_keys_128 = AesKeys::create(&[key128], AesKeySize::Key128, 0);
 
     let mut aes = aes_crypto;
-    aes.load_key(&aes_keys_128);
+    aes.load_key(&aes_keys_128)
+        .map_err(|_| "loading the key failed")?;
 
     let nonce = [];
     let ctr = [
@@ -140,8 +211,10 @@ fn inner_main() -> Result<(), &'static str> {
         0xf3, 0x00, 0x9c, 0xee,
     ];
 
-    aes.load_key(&aes_keys_128);
-    aes.ctr_encrypt(0, &nonce, &ctr, &input, &mut output);
+    aes.load_key(&aes_keys_128)
+        .map_err(|_| "loading the key failed")?;
+    aes.ctr_encrypt(0, &nonce, &ctr, &input, &mut output)
+        .map_err(|_| "CTR encryption failed")?;
 
     assert_eq!(output, expected);
 
@@ -151,9 +224,75 @@ fn inner_main() -> Result<(), &'static str> {
         &ctr,
         &output[..input.len()],
         &mut decrypted[..input.len()],
-    );
+    )
+    .map_err(|_| "CTR decryption failed")?;
     assert_eq!(input, decrypted);
 
+    // XOR'ing the keystream with the plaintext gives the CTR ciphertext.
+    let mut keystream = [0u8; 64];
+    aes.ctr_keystream(0, &nonce, &ctr, &mut keystream)
+        .map_err(|_| "CTR keystream failed")?;
+
+    for ((k, i), e) in keystream.iter().zip(input.iter()).zip(expected.iter()) {
+        assert_eq!(k ^ i, *e);
+    }
+
+    // Two 192-bit keys use two areas each, from area 2 on. Every key decrypts its own known
+    // block (SP 800-38A F.1.4 and FIPS-197 C.2) from its own area.
+    let key192_a = AesKey::Key192([
+        0x8e, 0x73, 0xb0, 0xf7, 0xda, 0x0e, 0x64, 0x52, 0xc8, 0x10, 0xf3, 0x2b, 0x80, 0x90, 0x79,
+        0xe5, 0x62, 0xf8, 0xea, 0xd2, 0x52, 0x2c, 0x6b, 0x7b,
+    ]);
+    let key192_b = AesKey::Key192([
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+    ]);
+    let aes_keys_192 = AesKeys::create(&[key192_a, key192_b], AesKeySize::Key192, 2);
+
+    if aes_keys_192.key_count() != 2 || aes_keys_192.key_area(1) != 4 {
+        return Err("wrong 192-bit key areas");
+    }
+
+    // The 128-bit key in area 0 is erased, since the key size changes.
+    aes.load_key_erasing(&aes_keys_192)
+        .map_err(|_| "loading the 192-bit keys failed")?;
+
+    let blocks: [([u8; 16], [u8; 16]); 2] = [
+        (
+            [
+                0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+                0x17, 0x2a,
+            ],
+            [
+                0xbd, 0x33, 0x4f, 0x1d, 0x6e, 0x45, 0xf2, 0x5f, 0xf7, 0x12, 0xa2, 0x14, 0x57, 0x1f,
+                0xa5, 0xcc,
+            ],
+        ),
+        (
+            [
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff,
+            ],
+            [
+                0xdd, 0xa9, 0x7c, 0xa4, 0x86, 0x4c, 0xdf, 0xe0, 0x6e, 0xaf, 0x70, 0xa0, 0xec, 0x0d,
+                0x71, 0x91,
+            ],
+        ),
+    ];
+
+    // A single block CBC with a zero IV is a single block ECB.
+    for (n, (plaintext, ciphertext)) in blocks.iter().enumerate() {
+        let mut block = [0u8; 16];
+        aes.cbc_decrypt(aes_keys_192.key_area(n), &[0; 16], ciphertext, &mut block)
+            .map_err(|_| "CBC decryption failed")?;
+
+        if &block != plaintext {
+            return Err("192-bit key decrypted from the wrong area");
+        }
+    }
+
+    rprintln!("Loaded and used two 192-bit keys");
+
     loop {
         asm::nop();
     }
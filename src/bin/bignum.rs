@@ -44,11 +44,9 @@ fn inner_main() -> Result<(), &'static str> {
 
     let mut sys_ctrl = sys_ctrl.freeze();
 
-    sys_ctrl.reset_aes();
-    sys_ctrl.clear_reset_aes();
+    sys_ctrl.pulse_reset_aes();
 
-    sys_ctrl.reset_pka();
-    sys_ctrl.clear_reset_pka();
+    sys_ctrl.pulse_reset_pka();
 
     let _crypto = Crypto::new(&mut periph.aes, &mut periph.pka);
 
@@ -83,6 +81,20 @@ fn inner_main() -> Result<(), &'static str> {
     let bignum_result = bignum1.mul(&bignum2).unwrap();
     rprintln!("{} * {} = {}", bignum1, bignum2, bignum_result);
 
+    let bignum_result = bignum2.mul_mod(&bignum3, &bignum1).unwrap();
+    rprintln!(
+        "{} * {} mod {} = {}",
+        bignum2,
+        bignum3,
+        bignum1,
+        bignum_result
+    );
+    let mut expected = BigNum::<16>::new(1);
+    expected.inner_mut()[0] = 2;
+    if bignum_result != expected {
+        return Err("bignum modular multiplication failed");
+    }
+
     //let (bignum_result, ) = bignum1.div(&bignum2).unwrap();
     //rprintln!("{} / {} = {} (remainder {})", bignum1, bignum2, bignum_result, bignum_result);
 
@@ -94,7 +106,7 @@ fn inner_main() -> Result<(), &'static str> {
 
     let mut base = BigNum::<16>::new(4);
     base.inner_mut().copy_from_slice(&[0x0fu32; 4]);
-    let bignum_result = bignum1.exp(&bignum2, &base);
+    let bignum_result = bignum1.exp(&bignum2, &base).unwrap();
     rprintln!("{}^{} mod {} = {}", base, bignum1, bignum2, bignum_result);
 
     rprintln!(
@@ -108,6 +120,23 @@ fn inner_main() -> Result<(), &'static str> {
         bignum2
     );
 
+    let bignum_result = bignum2.shl(36).unwrap();
+    rprintln!("{} << 36 = {}", bignum2, bignum_result);
+    if bignum_result.shr(36).unwrap().inner() != &bignum2.inner()[..4] {
+        return Err("shift left and right failed");
+    }
+
+    let bignum_result = bignum2.shr(4).unwrap();
+    rprintln!("{} >> 4 = {}", bignum2, bignum_result);
+
+    // Equality only looks at the value, not at the size.
+    if BigNum::<16>::one(1) != BigNum::<8>::one(4) || !BigNum::<16>::one(3).is_one() {
+        return Err("bignum equality failed");
+    }
+    if !BigNum::<16>::zero(4).is_zero() || bignum1 == bignum2 {
+        return Err("bignum equality failed");
+    }
+
     rprintln!("");
     rprintln!("Operations with raw slices:");
 
@@ -120,6 +149,12 @@ fn inner_main() -> Result<(), &'static str> {
     let len = Crypto::mul(num1, num2, &mut result).unwrap();
     rprintln!("Multiplication: {:0x?}", &result[..len]);
 
+    let len = Crypto::mulmod(num2, num2, num1, &mut result).unwrap();
+    rprintln!("Modular multiplication: {:0x?}", &result[..len]);
+    if result[..len] != [1, 0, 0, 0, 0] {
+        return Err("modular multiplication failed");
+    }
+
     //crypto.div(&mut num1, &mut num2, &mut result);
     //rprintln!("Division: {:0x?}", result);
 
@@ -130,10 +165,62 @@ fn inner_main() -> Result<(), &'static str> {
     rprintln!("Inverse modulo: {:0x?}", result);
 
     let base = [0x0fu32; 4];
-    Crypto::exp(num1, num2, base, &mut result);
-    rprintln!("Exponentiate: {:0x?}", result);
+    let len = Crypto::exp(num1, num2, base, &mut result).unwrap();
+    rprintln!("Exponentiate: {:0x?}", &result[..len]);
+
+    rprintln!("");
+    rsa_verify()?;
 
     loop {
         asm::bkpt();
     }
 }
+
+/// Verify an RSA signature with a 2048-bit public key: signature^e mod n == message.
+fn rsa_verify() -> Result<(), &'static str> {
+    let exponent = [65537u32];
+    let mut result = [0u32; 64];
+
+    let len = Crypto::exp(exponent, RSA_MODULUS, RSA_SIGNATURE, &mut result)
+        .map_err(|_| "RSA exponentiation failed")?;
+    rprintln!("RSA 2048-bit verification: {} words", len);
+
+    if len != RSA_MESSAGE.len() || result != RSA_MESSAGE {
+        return Err("RSA 2048-bit verification failed");
+    }
+
+    Ok(())
+}
+
+const RSA_MODULUS: [u32; 64] = [
+    0x3dc79f85, 0x9a2fea31, 0x43702cd7, 0xe280f907, 0x121b24c4, 0xe25722bb, 0xa61bc7c9, 0xad5b12ed,
+    0x89a06f88, 0x098f6fd2, 0xc8eb19d2, 0x4c877348, 0xc3c551ef, 0x298b6c12, 0xbf43a88b, 0x948cca37,
+    0xd3184af6, 0xa6255979, 0x817d7610, 0xab44b1a3, 0xaf3488fe, 0xae81bdfe, 0x2f716e61, 0x73c9623c,
+    0x6bc0ca68, 0x2672673e, 0xa31169ed, 0x49ec20d2, 0xdb9e1997, 0x2c1783ae, 0x523ff832, 0x6fa06154,
+    0x84dc1d8b, 0xf73f8c0c, 0x088ad209, 0xfb5bebf1, 0x5ef63804, 0xd2de7964, 0x2afa8681, 0x58ecdc8a,
+    0x33998c6f, 0x0a073c98, 0x272cb34d, 0xdcada1b0, 0xed16ce2a, 0x7f98ee80, 0xd7835881, 0x97e5630c,
+    0x255e38ee, 0x92a4837c, 0xe271cff2, 0xf088a452, 0x4ef8caba, 0x1c5b8171, 0x33167fd7, 0x76ec2492,
+    0xff62cc76, 0x7cc53b42, 0x8ebab064, 0x3e2536f9, 0xa44314e9, 0xb866e820, 0x898ca7e5, 0xbf1d5538,
+];
+
+const RSA_SIGNATURE: [u32; 64] = [
+    0xee9018b9, 0xaabb0641, 0xd5e8fe7f, 0xdbb785c3, 0x16e66615, 0xdcb30643, 0x0b7207fb, 0xc6653b08,
+    0x7131e47e, 0x21eeeab2, 0xb190e07a, 0xfc1f6780, 0xd39453ef, 0xcaf9d064, 0xca31ebc6, 0xb2e237cf,
+    0x807e29dc, 0x46e927a8, 0xbfa857e5, 0x2b749917, 0x2fdd16db, 0x0deee310, 0x0556b772, 0x3f15df6d,
+    0xa07afedc, 0xbf23129d, 0x317cbc5b, 0x0cd011bd, 0xdfbc0c07, 0x9cb620c5, 0xdb3e542d, 0xc47eb0e2,
+    0x6035c1a0, 0x5e618000, 0x14a76365, 0xf8c1d43f, 0xa48cd569, 0xe6d7126c, 0xc4609c48, 0x3f97402e,
+    0x9d05146e, 0x1a728f68, 0x08851531, 0xaadb8003, 0x55482e3b, 0x2509fc78, 0xdd1b6d1f, 0xbcd01af9,
+    0x3fb6278b, 0x76954127, 0x026c6d14, 0x8e039097, 0x4b21ba85, 0x8654e777, 0x832364a2, 0x25b2ff66,
+    0x7584ff6c, 0x3e7d6267, 0xfb77237e, 0x7423b433, 0x8bb6e16b, 0x9e6f9b30, 0xe7938cae, 0x371737e4,
+];
+
+const RSA_MESSAGE: [u32; 64] = [
+    0xe42a1b88, 0x05879662, 0xd425f67d, 0xbc392813, 0x01a14581, 0x0e556697, 0xe15ca7c9, 0xbbad4367,
+    0xa6b4b9f3, 0xea0f71a5, 0xd28e0125, 0x16f123e8, 0x561de828, 0xfee25be5, 0xbf575832, 0x61277d2e,
+    0x2a4c0ede, 0x02b6e154, 0x855e047c, 0xa07dd58f, 0x432209a7, 0xb3080f6b, 0x28529189, 0xab4877ad,
+    0xcdc2a23c, 0xf7aaf98f, 0x3f11ef30, 0x07598a99, 0xa04f0bb9, 0x034a69c5, 0xb4c76425, 0x6e5e2f64,
+    0x3b7c15d2, 0xf16b0c9b, 0xa97b185c, 0x9cec0e5b, 0x741ebb93, 0x5204fdfe, 0xb61083ef, 0x9e266576,
+    0xa359c27f, 0x6ec53de4, 0xf28d49b5, 0xc169a0af, 0xf0dd1a6f, 0xa8ea6ca9, 0x413bd62b, 0xc4393b7d,
+    0x272d1ae0, 0x08c40689, 0xe7c23ca6, 0x000e6cd8, 0x42781204, 0xc4150430, 0x3bd9dd7f, 0xbd58692a,
+    0xfbacdb64, 0x9bfd2820, 0x3c35734a, 0x6bbb67c9, 0xb9dafd7a, 0xbabb882e, 0xceb7370b, 0x00bc31bc,
+];
@@ -42,9 +42,9 @@ fn inner_main() -> Result<(), &'static str> {
     sys_ctrl.enable_aes_in_active_mode();
     sys_ctrl.enable_uart0_in_active_mode();
 
-    sys_ctrl.reset_aes();
     let mut sys_ctrl = sys_ctrl.freeze();
-    sys_ctrl.clear_reset_aes();
+
+    sys_ctrl.pulse_reset_aes();
 
     let mut sha256 = Crypto::new(&mut periph.aes, &mut periph.pka);
 
@@ -11,7 +11,10 @@ use panic_rtt_target as _;
 use rtt_target::{rprintln, rtt_init_print};
 use rtt_target::ChannelMode::BlockIfFull;
 
-use cc2538_hal::{crypto::*, sys_ctrl::*};
+use cc2538_hal::{
+    crypto::{bignum::BigNum, ecc::EcPoint, *},
+    sys_ctrl::*,
+};
 use cc2538_pac as pac;
 
 #[entry]
@@ -42,11 +45,9 @@ fn inner_main() -> Result<(), &'static str> {
 
     let mut sys_ctrl = sys_ctrl.freeze();
 
-    sys_ctrl.reset_aes();
-    sys_ctrl.clear_reset_aes();
+    sys_ctrl.pulse_reset_aes();
 
-    sys_ctrl.reset_pka();
-    sys_ctrl.clear_reset_pka();
+    sys_ctrl.pulse_reset_pka();
 
     let mut ecc_crypto = Crypto::new(&mut periph.aes, &mut periph.pka);
 
@@ -94,6 +95,62 @@ fn inner_main() -> Result<(), &'static str> {
         end - start
     );
 
+    // The same multiplication with big numbers: 6G + G must equal 7G.
+    let six = BigNum::<16>::from_slice(&scalar).map_err(|_| "bignum from slice failed")?;
+    let mut six_g = [0u32; 16];
+    ecc_crypto
+        .ecc_mul(&curve, six.inner(), &pointa, &mut six_g[..])
+        .map_err(|_| "ECC multiplication failed")?;
+    if six_g != result {
+        return Err("ECC multiplication with a bignum scalar failed");
+    }
+
+    let six_g = EcPoint::from_result(&curve, &six_g);
+    let mut x = six_g
+        .x_bignum::<8>()
+        .map_err(|_| "bignum from slice failed")?;
+    let mut y = six_g
+        .y_bignum::<8>()
+        .map_err(|_| "bignum from slice failed")?;
+    x.zero_extend(curve.size)
+        .map_err(|_| "zero extend failed")?;
+    y.zero_extend(curve.size)
+        .map_err(|_| "zero extend failed")?;
+
+    let mut seven_g_add = [0u32; 16];
+    ecc_crypto
+        .ecc_add(
+            &curve,
+            &EcPoint::from_bignums(&x, &y),
+            &pointa,
+            &mut seven_g_add[..],
+        )
+        .map_err(|_| "ECC addition failed")?;
+
+    let seven = six
+        .add(&BigNum::<16>::one(1))
+        .map_err(|_| "bignum addition failed")?;
+    let mut seven_g_mul = [0u32; 16];
+    ecc_crypto
+        .ecc_mul(&curve, seven.inner(), &pointa, &mut seven_g_mul[..])
+        .map_err(|_| "ECC multiplication failed")?;
+
+    if seven_g_add != seven_g_mul {
+        return Err("6G + G is not 7G");
+    }
+
+    // Reduce the x coordinate modulo the order of the curve, like the r of ECDSA.
+    let order = BigNum::<8>::from_slice(curve.order).map_err(|_| "bignum from slice failed")?;
+    let r = EcPoint::from_result(&curve, &seven_g_mul)
+        .x_bignum::<16>()
+        .and_then(|x| x.modulo(&order))
+        .map_err(|_| "bignum modulo failed")?;
+    rprintln!("x(7G) mod n: {}", r);
+
+    if r.compare(&order) != Some(core::cmp::Ordering::Less) {
+        return Err("x(7G) mod n is not reduced");
+    }
+
     loop {
         asm::nop();
     }
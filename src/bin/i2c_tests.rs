@@ -0,0 +1,117 @@
+#![no_main]
+#![no_std]
+
+use cortex_m::asm;
+use cortex_m_rt as rt;
+use rt::entry;
+
+use panic_rtt_target as _;
+
+use rtt_target::{rprintln, rtt_init_print};
+
+use cc2538_hal::{gpio::*, i2c::*, ioc::*, sys_ctrl::*};
+use cc2538_pac as pac;
+
+/// Address of a 24C02 compatible EEPROM, with SDA on PB4 and SCL on PB5.
+const EEPROM_ADDR: u8 = 0x50;
+/// Word address of the test pattern, aligned to an 8 byte page.
+const PATTERN_ADDR: u8 = 0x10;
+const PATTERN: [u8; 8] = [0xa5, 0x5a, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+
+    match inner_main() {
+        Ok(()) => cortex_m::peripheral::SCB::sys_reset(),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn inner_main() -> Result<(), &'static str> {
+    let periph = unsafe { pac::Peripherals::steal() };
+
+    // Setup the clock
+    let mut sys_ctrl = periph.sys_ctrl.constrain();
+    sys_ctrl.set_sys_div(ClockDiv::Clock32Mhz);
+    sys_ctrl.set_io_div(ClockDiv::Clock32Mhz);
+    sys_ctrl.enable_i2c_in_active_mode();
+
+    let sys_ctrl = sys_ctrl.freeze();
+
+    let mut ioc = periph.ioc.split();
+    let mut gpiob = periph.gpio_b.split();
+
+    let _sda = gpiob.pb4.into_i2c_sda(
+        &mut gpiob.dir,
+        &mut gpiob.afsel,
+        &mut ioc.pb4_sel,
+        &mut ioc.pb4_over,
+        &mut ioc.i2cmssda,
+    );
+    let _scl = gpiob.pb5.into_i2c_scl(
+        &mut gpiob.dir,
+        &mut gpiob.afsel,
+        &mut ioc.pb5_sel,
+        &mut ioc.pb5_over,
+        &mut ioc.i2cmsscl,
+    );
+
+    let i2c = periph.i2cm.take().enable();
+    i2c.set_bit_rate(100_000, sys_ctrl.config());
+
+    if !i2c.device_present(EEPROM_ADDR) {
+        return Err("no EEPROM found");
+    }
+
+    // Page write of the pattern, preceded by its word address.
+    let mut page = [0u8; 9];
+    page[0] = PATTERN_ADDR;
+    page[1..].copy_from_slice(&PATTERN);
+    i2c.burst_write(EEPROM_ADDR, &page)
+        .map_err(|_| "writing the pattern failed")?;
+
+    // The EEPROM does not acknowledge its address until the write cycle completed.
+    while !i2c.device_present(EEPROM_ADDR) {}
+
+    // Read the first half of the pattern.
+    let mut buffer = [0u8; 4];
+    i2c.burst_write(EEPROM_ADDR, &[PATTERN_ADDR])
+        .map_err(|_| "setting the word address failed")?;
+    i2c.burst_read(EEPROM_ADDR, &mut buffer)
+        .map_err(|_| "burst read failed")?;
+    rprintln!("Burst read: {:02x?}", buffer);
+
+    if buffer != PATTERN[..4] {
+        return Err("burst read returned wrong data");
+    }
+
+    // A current address read returns the byte after the last one that was clocked out, so it
+    // only returns the next byte of the pattern when the last byte was NACKed.
+    let next = i2c
+        .single_read(EEPROM_ADDR)
+        .map_err(|_| "current address read failed")?;
+    rprintln!("Current address read: {:02x}", next);
+
+    if next != PATTERN[4] {
+        return Err("burst read consumed an extra byte");
+    }
+
+    // The last byte of a burst read is NACKed as well when the read is only two bytes long.
+    let mut buffer = [0u8; 2];
+    i2c.burst_read(EEPROM_ADDR, &mut buffer)
+        .map_err(|_| "burst read failed")?;
+    let next = i2c
+        .single_read(EEPROM_ADDR)
+        .map_err(|_| "current address read failed")?;
+
+    if buffer != PATTERN[5..7] || next != PATTERN[7] {
+        return Err("burst read consumed an extra byte");
+    }
+
+    rprintln!("I2C EEPROM test passed");
+
+    loop {
+        asm::bkpt();
+    }
+}
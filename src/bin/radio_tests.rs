@@ -0,0 +1,74 @@
+#![no_main]
+#![no_std]
+
+use cortex_m::asm;
+use cortex_m_rt as rt;
+use rt::entry;
+
+use panic_rtt_target as _;
+
+use rtt_target::{rprintln, rtt_init_print};
+use rtt_target::ChannelMode::BlockIfFull;
+
+use cc2538_hal::radio::Channel;
+use cc2538_hal::{dma::*, radio::*, sys_ctrl::*};
+use cc2538_pac as pac;
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!(BlockIfFull);
+
+    match inner_main() {
+        Ok(()) => cortex_m::peripheral::SCB::sys_reset(),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn inner_main() -> Result<(), &'static str> {
+    let mut periph = unsafe { pac::Peripherals::steal() };
+
+    // Setup the clock
+    let mut sys_ctrl = periph.sys_ctrl.constrain();
+    sys_ctrl.set_sys_div(ClockDiv::Clock32Mhz);
+    sys_ctrl.set_io_div(ClockDiv::Clock32Mhz);
+    sys_ctrl.enable_radio_in_active_mode();
+    let _sys_ctrl = sys_ctrl.freeze();
+
+    let dma = periph.udma.constrain().enable();
+    let tx_channel = dma.get_channel(0, false);
+    let rx_channel = dma.get_channel(1, false);
+
+    let mut radio = RadioDriver::new(
+        &mut periph.rfcore_ffsm,
+        &mut periph.rfcore_xreg,
+        &mut periph.rfcore_sfr,
+        &mut periph.ana_regs,
+        tx_channel,
+        rx_channel,
+    );
+
+    for value in [0x0000, 0x00ff, 0xff00, 0xabcd, 0xffff] {
+        radio.set_pan_id(value as u32);
+        assert_eq!(radio.get_pan_id(), value);
+
+        radio.set_short_address(value);
+        assert_eq!(radio.get_short_address(), value);
+    }
+
+    for (channel, frequency) in [
+        (Channel::Channel11, 2_405_000_000),
+        (Channel::Channel18, 2_440_000_000),
+        (Channel::Channel26, 2_480_000_000),
+    ] {
+        radio.set_channel(channel);
+        assert_eq!(radio.current_channel(), Some(channel));
+        assert_eq!(radio.current_frequency(), frequency);
+    }
+
+    rprintln!("Done!");
+    rprintln!("Tests seems correct!");
+
+    loop {
+        asm::nop();
+    }
+}
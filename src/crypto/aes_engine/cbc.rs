@@ -0,0 +1,76 @@
+use cc2538_pac::aes;
+
+use super::Crypto;
+use super::CryptoError;
+
+pub struct AesCbc {}
+
+impl Crypto<'_> {
+    /// Encrypt `data_in` using AES-CBC.
+    ///
+    /// The length of `data_in` must be a multiple of 16 bytes. The IV for a next call that
+    /// continues the chain can be read with [`Crypto::read_context`].
+    pub fn cbc_encrypt(
+        &mut self,
+        key_index: u32,
+        iv: &[u8],
+        data_in: &[u8],
+        data_out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        debug_assert!(data_in.len().is_multiple_of(16));
+
+        if Self::is_aes_in_use() {
+            return Err(CryptoError::AesBusy);
+        }
+
+        let ctrl = |aes: &aes::RegisterBlock| {
+            aes.aes_ctrl().write(|w| {
+                w.save_context()
+                    .set_bit()
+                    .cbc()
+                    .set_bit()
+                    .direction()
+                    .set_bit()
+            });
+        };
+
+        let mut block = [0u8; 16];
+        self.copy_iv_field(&mut block, iv);
+
+        self.auth_crypt(ctrl, key_index, Some(&block), None, data_in, data_out)
+    }
+
+    /// Decrypt `data_in` using AES-CBC.
+    ///
+    /// The length of `data_in` must be a multiple of 16 bytes. The IV for a next call that
+    /// continues the chain can be read with [`Crypto::read_context`].
+    pub fn cbc_decrypt(
+        &mut self,
+        key_index: u32,
+        iv: &[u8],
+        data_in: &[u8],
+        data_out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        debug_assert!(data_in.len().is_multiple_of(16));
+
+        if Self::is_aes_in_use() {
+            return Err(CryptoError::AesBusy);
+        }
+
+        let ctrl = |aes: &aes::RegisterBlock| {
+            aes.aes_ctrl().write(|w| {
+                w.save_context()
+                    .set_bit()
+                    .cbc()
+                    .set_bit()
+                    .direction()
+                    .clear_bit()
+            });
+        };
+
+        let mut block = [0u8; 16];
+        self.copy_iv_field(&mut block, iv);
+
+        self.auth_crypt(ctrl, key_index, Some(&block), None, data_in, data_out)
+    }
+}
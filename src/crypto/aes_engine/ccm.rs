@@ -3,6 +3,7 @@ use cc2538_pac::aes;
 use super::super::CtrWidth;
 use super::AesEngine;
 use super::Crypto;
+use super::CryptoError;
 
 pub struct AesCcm {}
 
@@ -42,19 +43,12 @@ impl Crypto<'_> {
         nonce: &[u8],
         data_in: &[u8],
         data_out: &mut [u8],
-    ) {
+    ) -> Result<(), CryptoError> {
         if Self::is_aes_in_use() {
-            return;
+            return Err(CryptoError::AesBusy);
         }
 
-        // Prepare the IV
-        // The first part is the length of the data minus 1.
-        // The following part is the nonce.
-        // And the rest is the counter.
-        let mut iv = [0u8; 16];
-        iv[0] = ccm_info.len_field_size - 1;
-        iv[1..][..Self::CCM_NONCE_LEN - ccm_info.len_field_size as usize].copy_from_slice(nonce);
-        iv[16 - ccm_info.len_field_size as usize..].fill_with(|| 0);
+        let iv = self.ccm_iv(ccm_info, nonce);
 
         self.auth_crypt(
             ctrl,
@@ -63,9 +57,31 @@ impl Crypto<'_> {
             ccm_info.adata,
             data_in,
             data_out,
+        )
+    }
+
+    /// Build the counter block A0 from the nonce.
+    fn ccm_iv(&self, ccm_info: &AesCcmInfo, nonce: &[u8]) -> [u8; 16] {
+        // Prepare the IV
+        // The first part is the length of the data minus 1.
+        // The following part is the nonce.
+        // And the rest is the counter.
+        let mut iv = [0u8; 16];
+        iv[0] = ccm_info.len_field_size - 1;
+        self.copy_iv_field(
+            &mut iv[1..][..Self::CCM_NONCE_LEN - ccm_info.len_field_size as usize],
+            nonce,
         );
+        iv[16 - ccm_info.len_field_size as usize..].fill_with(|| 0);
+        iv
     }
 
+    /// Encrypt and authenticate `data_in` using AES-CCM.
+    ///
+    /// A single DMA transfer moves at most [`MAX_DMA_LEN`](super::MAX_DMA_LEN) bytes. Bigger
+    /// added authentication data and payloads are split up in transfers of that size, but they
+    /// still have to be in memory as one slice. Use [`Crypto::ccm_encrypt_start`] to feed them
+    /// in parts instead.
     pub fn ccm_encrypt(
         &mut self,
         ccm_info: &AesCcmInfo,
@@ -73,7 +89,7 @@ impl Crypto<'_> {
         data_in: &[u8],
         data_out: &mut [u8],
         tag: &mut [u8],
-    ) {
+    ) -> Result<(), CryptoError> {
         let m = (ccm_info.auth_field_size.max(2) - 2) >> 1;
         let l = ccm_info.len_field_size - 1;
 
@@ -96,17 +112,22 @@ impl Crypto<'_> {
             });
         };
 
-        self.ccm_crypt(ctrl, ccm_info, nonce, data_in, data_out);
+        self.ccm_crypt(ctrl, ccm_info, nonce, data_in, data_out)?;
         self.read_tag(tag);
+        Ok(())
     }
 
+    /// Decrypt `data_in` using AES-CCM.
+    ///
+    /// See [`Crypto::ccm_encrypt`] for the handling of data bigger than a single DMA transfer,
+    /// and [`Crypto::ccm_decrypt_start`] to feed the data in parts.
     pub fn ccm_decrypt(
         &mut self,
         ccm_info: &AesCcmInfo,
         nonce: &[u8],
         data_in: &[u8],
         data_out: &mut [u8],
-    ) {
+    ) -> Result<(), CryptoError> {
         let m = (ccm_info.auth_field_size.max(2) - 2) >> 1;
         let l = ccm_info.len_field_size - 1;
 
@@ -129,6 +150,148 @@ impl Crypto<'_> {
             });
         };
 
-        self.ccm_crypt(ctrl, ccm_info, nonce, data_in, data_out);
+        self.ccm_crypt(ctrl, ccm_info, nonce, data_in, data_out)
+    }
+}
+
+impl<'p> Crypto<'p> {
+    /// Start an AES-CCM encryption of which the data is fed in multiple parts.
+    ///
+    /// The engine is configured for `adata_len` bytes of added authentication data and
+    /// `data_len` bytes of payload, which are then fed with [`CcmOperation::update_adata`] and
+    /// [`CcmOperation::update`]. The added authentication data of `ccm_info` is not used. The
+    /// ciphertext and the tag are the same as when the data is given to
+    /// [`Crypto::ccm_encrypt`] at once.
+    pub fn ccm_encrypt_start<'c>(
+        &'c mut self,
+        ccm_info: &AesCcmInfo,
+        nonce: &[u8],
+        adata_len: usize,
+        data_len: usize,
+    ) -> Result<CcmOperation<'c, 'p>, CryptoError> {
+        self.ccm_start(true, ccm_info, nonce, adata_len, data_len)
+    }
+
+    /// Start an AES-CCM decryption of which the data is fed in multiple parts.
+    ///
+    /// See [`Crypto::ccm_encrypt_start`].
+    pub fn ccm_decrypt_start<'c>(
+        &'c mut self,
+        ccm_info: &AesCcmInfo,
+        nonce: &[u8],
+        adata_len: usize,
+        data_len: usize,
+    ) -> Result<CcmOperation<'c, 'p>, CryptoError> {
+        self.ccm_start(false, ccm_info, nonce, adata_len, data_len)
+    }
+
+    fn ccm_start<'c>(
+        &'c mut self,
+        encrypt: bool,
+        ccm_info: &AesCcmInfo,
+        nonce: &[u8],
+        adata_len: usize,
+        data_len: usize,
+    ) -> Result<CcmOperation<'c, 'p>, CryptoError> {
+        let m = (ccm_info.auth_field_size.max(2) - 2) >> 1;
+        let l = ccm_info.len_field_size - 1;
+
+        let ctrl = |aes: &aes::RegisterBlock| unsafe {
+            aes.aes_ctrl().modify(|_, w| {
+                w.save_context()
+                    .set_bit()
+                    .ccm_m()
+                    .bits(m)
+                    .ccm_l()
+                    .bits(l)
+                    .ccm()
+                    .set_bit()
+                    .ctr_width()
+                    .bits(CtrWidth::Width128 as u8)
+                    .ctr()
+                    .set_bit()
+                    .direction()
+                    .bit(encrypt)
+            });
+        };
+
+        let iv = self.ccm_iv(ccm_info, nonce);
+        self.auth_start(
+            ctrl,
+            ccm_info.key_index,
+            Some(&iv),
+            Some(adata_len),
+            data_len,
+        )?;
+
+        Ok(CcmOperation {
+            crypto: self,
+            adata_left: adata_len,
+            data_left: data_len,
+        })
+    }
+}
+
+/// An AES-CCM operation of which the data is fed in multiple parts.
+///
+/// All added authentication data is fed before the payload. Every part, except the last part of
+/// the added authentication data and the last part of the payload, must be a multiple of 16
+/// bytes, such that the engine keeps its counter and tag context between the parts. A part can
+/// be bigger than a single DMA transfer ([`MAX_DMA_LEN`](super::MAX_DMA_LEN) bytes).
+///
+/// The operation must be fed completely and finished with [`CcmOperation::finish`], otherwise
+/// the AES engine keeps waiting for the rest of the data.
+pub struct CcmOperation<'c, 'p> {
+    crypto: &'c mut Crypto<'p>,
+    adata_left: usize,
+    data_left: usize,
+}
+
+impl CcmOperation<'_, '_> {
+    /// Check that a part of `len` bytes fits in the `left` bytes of the operation.
+    fn check_part(len: usize, left: usize) -> Result<(), CryptoError> {
+        if len > left || (len != left && !len.is_multiple_of(16)) {
+            return Err(CryptoError::InvalidLength);
+        }
+
+        Ok(())
+    }
+
+    /// Feed the next part of the added authentication data.
+    pub fn update_adata(&mut self, adata: &[u8]) -> Result<(), CryptoError> {
+        Self::check_part(adata.len(), self.adata_left)?;
+
+        self.crypto.auth_feed_adata(adata)?;
+        self.adata_left -= adata.len();
+        Ok(())
+    }
+
+    /// Feed the next part of the payload, writing the result to `data_out`.
+    ///
+    /// Returns [`CryptoError::InvalidLength`] when not all added authentication data was fed
+    /// yet, or when `data_out` is shorter than `data_in`.
+    pub fn update(&mut self, data_in: &[u8], data_out: &mut [u8]) -> Result<(), CryptoError> {
+        if self.adata_left != 0 || data_out.len() < data_in.len() {
+            return Err(CryptoError::InvalidLength);
+        }
+        Self::check_part(data_in.len(), self.data_left)?;
+
+        self.crypto.auth_feed(data_in, &data_out[..data_in.len()])?;
+        self.data_left -= data_in.len();
+        Ok(())
+    }
+
+    /// Wait until the operation is finished and read the tag.
+    ///
+    /// When decrypting, the tag is computed over the decrypted payload and must be compared
+    /// with the received tag. Returns [`CryptoError::InvalidLength`] when not all data was fed.
+    pub fn finish(self, tag: &mut [u8]) -> Result<(), CryptoError> {
+        if self.adata_left != 0 || self.data_left != 0 {
+            return Err(CryptoError::InvalidLength);
+        }
+
+        self.crypto.auth_finish()?;
+        self.crypto.read_tag(tag);
+        Ok(())
     }
 }
@@ -3,9 +3,115 @@ use cc2538_pac::aes;
 //use super::super::CtrWidth;
 use super::AesEngine;
 use super::Crypto;
+use super::CryptoError;
 
 pub struct AesCtr {}
 
+/// AES-CTR state that keeps the counter between calls.
+///
+/// After every chunk the counter is advanced by the amount of 16-byte blocks that were used, such
+/// that successive chunks form one continuous keystream. The remainder of the keystream block
+/// used by a chunk that is not a multiple of 16 bytes is discarded, so only the last chunk
+/// should have a different length.
+#[derive(Debug, Clone)]
+pub struct CtrStream {
+    key_index: u32,
+    iv: [u8; 16],
+    ctr_len: usize,
+}
+
+impl CtrStream {
+    /// Create a new stream from a nonce and the initial value of the counter.
+    ///
+    /// The counter must be 4, 8, 12 or 16 bytes long and together with the nonce 16 bytes. Both
+    /// are in the byte order set with [`Crypto::set_iv_byte_order`].
+    pub fn new(crypto: &Crypto, key_index: u32, nonce: &[u8], ctr: &[u8]) -> Self {
+        assert!(matches!(ctr.len(), 4 | 8 | 12 | 16));
+        assert_eq!(nonce.len() + ctr.len(), 16);
+
+        Self {
+            key_index,
+            iv: crypto.ctr_iv(nonce, ctr),
+            ctr_len: ctr.len(),
+        }
+    }
+
+    /// Return the current value of the counter, in the native order of the AES engine
+    /// ([`ByteOrder::BigEndian`](super::ByteOrder)).
+    pub fn counter(&self) -> &[u8] {
+        &self.iv[16 - self.ctr_len..]
+    }
+
+    /// Encrypt the next chunk of the stream.
+    ///
+    /// The counter is only advanced when the chunk was encrypted.
+    pub fn encrypt_chunk(
+        &mut self,
+        crypto: &mut Crypto,
+        mdata_in: &[u8],
+        mdata_out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        crypto.ctr_crypt(
+            true,
+            self.key_index,
+            &self.iv,
+            self.ctr_len,
+            mdata_in,
+            mdata_out,
+        )?;
+        self.advance(mdata_in.len());
+        Ok(())
+    }
+
+    /// Decrypt the next chunk of the stream.
+    ///
+    /// The counter is only advanced when the chunk was decrypted.
+    pub fn decrypt_chunk(
+        &mut self,
+        crypto: &mut Crypto,
+        mdata_in: &[u8],
+        mdata_out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        crypto.ctr_crypt(
+            false,
+            self.key_index,
+            &self.iv,
+            self.ctr_len,
+            mdata_in,
+            mdata_out,
+        )?;
+        self.advance(mdata_in.len());
+        Ok(())
+    }
+
+    /// Advance the counter by the amount of blocks used for `len` bytes.
+    ///
+    /// The counter is a big-endian value that wraps around within its width, like the counter
+    /// of the AES engine, and never carries into the nonce.
+    fn advance(&mut self, len: usize) {
+        advance_counter(&mut self.iv, self.ctr_len, len);
+    }
+}
+
+/// Advance the counter in the last `ctr_len` bytes of `iv` by the amount of blocks used for
+/// `len` bytes.
+fn advance_counter(iv: &mut [u8; 16], ctr_len: usize, len: usize) {
+    let mut carry = len.div_ceil(16) as u128;
+
+    for byte in iv[16 - ctr_len..].iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+
+        let sum = *byte as u128 + (carry & 0xff);
+        *byte = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+}
+
+/// The amount of keystream bytes generated per run of the AES engine.
+const KEYSTREAM_CHUNK_LEN: usize = 64;
+
 impl Crypto<'_> {
     pub fn ctr_encrypt(
         &mut self,
@@ -14,58 +120,96 @@ impl Crypto<'_> {
         ctr: &[u8],
         mdata_in: &[u8],
         mdata_out: &mut [u8],
-    ) {
-        if Self::is_aes_in_use() {
-            return;
+    ) -> Result<(), CryptoError> {
+        let iv = self.ctr_iv(nonce, ctr);
+        self.ctr_crypt(true, key_index, &iv, ctr.len(), mdata_in, mdata_out)
+    }
+
+    pub fn ctr_decrypt(
+        &mut self,
+        key_index: u32,
+        nonce: &[u8],
+        ctr: &[u8],
+        mdata_in: &[u8],
+        mdata_out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        let iv = self.ctr_iv(nonce, ctr);
+        self.ctr_crypt(false, key_index, &iv, ctr.len(), mdata_in, mdata_out)
+    }
+
+    /// Generate the raw AES-CTR keystream, which is the same as encrypting zeros.
+    ///
+    /// The keystream can be XOR'ed with data in software, for example when the data is not
+    /// contiguous in memory and can not be given to the DMA as one slice. XOR'ing the data with
+    /// the keystream gives the same result as [`Self::ctr_encrypt`] and [`Self::ctr_decrypt`]
+    /// with the same key, nonce and counter.
+    ///
+    /// **NOTE**: reusing a (key, nonce, counter) triple for the keystream is as dangerous as
+    /// reusing it for encryption: XOR'ing two ciphertexts made with the same keystream reveals
+    /// the XOR of the plaintexts.
+    pub fn ctr_keystream(
+        &mut self,
+        key_index: u32,
+        nonce: &[u8],
+        ctr: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), CryptoError> {
+        let mut iv = self.ctr_iv(nonce, ctr);
+        let zeros = [0u8; KEYSTREAM_CHUNK_LEN];
+
+        // The chunks are a multiple of the block size, such that advancing the counter
+        // continues the keystream where the previous chunk stopped.
+        for chunk in out.chunks_mut(KEYSTREAM_CHUNK_LEN) {
+            self.ctr_crypt(
+                true,
+                key_index,
+                &iv,
+                ctr.len(),
+                &zeros[..chunk.len()],
+                chunk,
+            )?;
+            advance_counter(&mut iv, ctr.len(), chunk.len());
         }
 
-        let ctrl = |aes: &aes::RegisterBlock| unsafe {
-            aes.aes_ctrl().write(|w| {
-                w.ctr_width()
-                    .bits((ctr.len() >> 2) as u8 - 1)
-                    .ctr()
-                    .set_bit()
-                    .direction()
-                    .set_bit()
-            });
-        };
+        Ok(())
+    }
 
+    /// Build the IV from the nonce and the counter, in the native order of the AES engine.
+    fn ctr_iv(&self, nonce: &[u8], ctr: &[u8]) -> [u8; 16] {
         let mut iv = [0u8; 16];
         let nonce_len = nonce.len();
-        iv[..nonce_len].copy_from_slice(nonce);
-        iv[nonce_len..].copy_from_slice(ctr);
-
-        self.auth_crypt(ctrl, key_index, Some(&iv), None, mdata_in, mdata_out)
+        self.copy_iv_field(&mut iv[..nonce_len], nonce);
+        self.copy_iv_field(&mut iv[nonce_len..], ctr);
+        iv
     }
 
-    pub fn ctr_decrypt(
+    /// Run AES-CTR with an IV that is already in the native order of the AES engine.
+    fn ctr_crypt(
         &mut self,
+        encrypt: bool,
         key_index: u32,
-        nonce: &[u8],
-        ctr: &[u8],
+        iv: &[u8; 16],
+        ctr_len: usize,
         mdata_in: &[u8],
         mdata_out: &mut [u8],
-    ) {
+    ) -> Result<(), CryptoError> {
         if Self::is_aes_in_use() {
-            return;
+            return Err(CryptoError::AesBusy);
         }
 
         let ctrl = |aes: &aes::RegisterBlock| unsafe {
             aes.aes_ctrl().write(|w| {
-                w.ctr_width()
-                    .bits((ctr.len() >> 2) as u8 - 1)
+                w.save_context()
+                    .set_bit()
+                    .ctr_width()
+                    .bits((ctr_len >> 2) as u8 - 1)
                     .ctr()
                     .set_bit()
                     .direction()
-                    .clear_bit()
+                    .bit(encrypt)
             });
         };
 
-        let mut iv = [0u8; 16];
-        let nonce_len = nonce.len();
-        iv[..nonce_len].copy_from_slice(nonce);
-        iv[nonce_len..].copy_from_slice(ctr);
-
-        self.auth_crypt(ctrl, key_index, Some(&iv), None, mdata_in, mdata_out)
+        self.auth_crypt(ctrl, key_index, Some(iv), None, mdata_in, mdata_out)
     }
 }
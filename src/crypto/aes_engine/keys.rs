@@ -1,18 +1,32 @@
+use super::ByteOrder;
+
 #[derive(Debug, Clone, Copy)]
 pub struct AesKeys {
     pub keys: [u8; 128],   // 1024 bits of memory (8 128-bit keys)
     pub sizes: AesKeySize, // The type of keys stored
-    pub count: u8,         // How many keys are stored
+    pub count: u8,         // How many 128-bit areas are used by the keys
     pub start_area: u8,    // The start area in 128 bits
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AesKeySize {
     Key128 = 0b01,
     Key192 = 0b10,
     Key256 = 0b11,
 }
 
+impl AesKeySize {
+    /// The amount of 128-bit key areas used by one key of this size.
+    ///
+    /// 192-bit keys are padded to 256 bits and, like 256-bit keys, use two areas.
+    pub const fn areas(self) -> u8 {
+        match self {
+            AesKeySize::Key128 => 1,
+            AesKeySize::Key192 | AesKeySize::Key256 => 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum AesKey {
     Key128([u8; 16]),
@@ -20,10 +34,53 @@ pub enum AesKey {
     Key256([u8; 32]),
 }
 
+impl AesKey {
+    /// The size of the key.
+    pub const fn size(&self) -> AesKeySize {
+        match self {
+            AesKey::Key128(_) => AesKeySize::Key128,
+            AesKey::Key192(_) => AesKeySize::Key192,
+            AesKey::Key256(_) => AesKeySize::Key256,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self {
+            AesKey::Key128(k) => k,
+            AesKey::Key192(k) => k,
+            AesKey::Key256(k) => k,
+        }
+    }
+}
+
 impl AesKeys {
     // XXX Create a better key management system for AES
     /// Create a correctly aligned key buffer for the AES engine.
+    ///
+    /// The keys are in the native order of the AES engine ([`ByteOrder::BigEndian`]). See
+    /// [`AesKeys::create_with_byte_order`] for the layout of the keys.
     pub fn create(keys: &[AesKey], sizes: AesKeySize, start_area: u8) -> Self {
+        Self::create_with_byte_order(keys, sizes, start_area, ByteOrder::BigEndian)
+    }
+
+    /// Create a correctly aligned key buffer for the AES engine, from keys in the given byte
+    /// order.
+    ///
+    /// The key store only holds keys of one size, so every key must have the size `sizes`.
+    /// A 128-bit key uses one key area, 192-bit and 256-bit keys use two areas and must start
+    /// at an even area. The keys are stored in consecutive areas from `start_area` on, use
+    /// [`AesKeys::key_area`] to get the area of each key.
+    ///
+    /// # Panics
+    ///
+    /// Panics when a key does not have the size `sizes`, when `start_area` is not aligned to
+    /// the size of the keys or when the keys do not fit in the 8 areas of the key store.
+    pub fn create_with_byte_order(
+        keys: &[AesKey],
+        sizes: AesKeySize,
+        start_area: u8,
+        order: ByteOrder,
+    ) -> Self {
         let mut aligned = AesKeys {
             keys: [0; 128],
             sizes,
@@ -31,27 +88,39 @@ impl AesKeys {
             start_area,
         };
 
-        let mut offset = 0;
-        for k in keys.iter() {
-            match k {
-                AesKey::Key128(k) => {
-                    aligned.keys[offset..offset + k.len()].copy_from_slice(k);
-                    offset += 128 / 8;
-                    aligned.count += 1;
-                }
-                AesKey::Key192(k) => {
-                    aligned.keys[offset..offset + k.len()].copy_from_slice(k);
-                    offset += 128 / 8 * 2;
-                    aligned.count += 2;
-                }
-                AesKey::Key256(k) => {
-                    aligned.keys[offset..offset + k.len()].copy_from_slice(k);
-                    offset += 128 / 8 * 2;
-                    aligned.count += 2;
-                }
-            }
+        let areas = sizes.areas();
+        assert!(
+            start_area.is_multiple_of(areas),
+            "key area not aligned to the key size"
+        );
+        assert!(
+            start_area as usize + keys.len() * areas as usize <= 8,
+            "keys do not fit in the key store"
+        );
+
+        for (i, k) in keys.iter().enumerate() {
+            assert!(k.size() == sizes, "key size differs from the key set size");
+
+            // Every key starts at the first byte of its area(s), 192-bit keys are zero padded.
+            let offset = i * areas as usize * 16;
+            let k = k.bytes();
+            order.copy_to_native(&mut aligned.keys[offset..offset + k.len()], k);
         }
 
+        aligned.count = keys.len() as u8 * areas;
+
         aligned
     }
+
+    /// The number of keys in the set.
+    pub fn key_count(&self) -> usize {
+        (self.count / self.sizes.areas()) as usize
+    }
+
+    /// The key area of the `n`th key of the set, which is the `key_index` to use with the AES
+    /// operations.
+    pub fn key_area(&self, n: usize) -> u32 {
+        debug_assert!(n < self.key_count());
+        self.start_area as u32 + (n as u32) * self.sizes.areas() as u32
+    }
 }
@@ -4,34 +4,96 @@ use core::marker::PhantomData;
 use cc2538_pac::aes;
 
 use super::Crypto;
+use super::CryptoError;
 use super::CryptoMode;
+use super::Deadline;
 use super::NotSpecified;
 
 pub mod keys;
 use keys::AesKeys;
 
+pub mod cbc;
 pub mod ccm;
 pub mod ctr;
 
+use cbc::AesCbc;
 use ccm::AesCcm;
 use ctr::AesCtr;
 
 pub struct AesEngine<Type> {
     _type: PhantomData<Type>,
 }
-pub struct AesCbc {}
 pub struct AesCbcMac {}
 pub struct AesEcb {}
 pub struct AesGcm {}
 
+/// The order of the bytes of the keys, IVs, nonces and counters passed to the AES engine.
+///
+/// The AES engine natively uses the order of the standards and the NIST test vectors: the bytes
+/// are processed as a stream, the first byte being the most significant byte of the block or
+/// counter. This is [`ByteOrder::BigEndian`]. With [`ByteOrder::LittleEndian`], every key, IV,
+/// nonce and counter is reversed before it is given to the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    #[default]
+    BigEndian,
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// Copy `src` into `dst`, converting it to the native order of the AES engine.
+    pub(crate) fn copy_to_native(self, dst: &mut [u8], src: &[u8]) {
+        dst.copy_from_slice(src);
+        if self == ByteOrder::LittleEndian {
+            dst.reverse();
+        }
+    }
+}
+
+/// Maximum amount of bytes that is moved in a single DMA transfer to or from the AES engine.
+///
+/// The DMA length register is only 16 bits wide. Bigger buffers are fed to the engine in
+/// multiple transfers of at most this size. The value is a multiple of the AES block size, such
+/// that the engine keeps its counter and tag context between the transfers of one operation.
+pub const MAX_DMA_LEN: usize = 0xfff0;
+
 impl Crypto<'_> {
-    /// Workaround for AES registers not retained after PM2.
+    /// Re-initialise the AES registers that are not retained in PM2 and PM3.
+    ///
+    /// This is done automatically before the first AES operation after waking up with
+    /// [`SysCtrl::enter_power_mode`](crate::sys_ctrl::SysCtrl::enter_power_mode). Call this
+    /// manually when entering PM2 or PM3 by other means.
     #[inline]
-    fn workaround(&mut self) {
+    pub fn reinit_after_wake(&mut self) {
         let aes = Self::aes();
         aes.ctrl_int_cfg().write(|w| w.level().set_bit());
         aes.ctrl_int_en()
             .write(|w| w.dma_in_done().set_bit().result_av().set_bit());
+
+        self.wakeups = crate::sys_ctrl::retention_lost_count();
+    }
+
+    /// Re-initialise the AES registers when the chip woke up from PM2 or PM3 since they were
+    /// last initialised.
+    #[inline]
+    fn reinit_if_woken(&mut self) {
+        if self.wakeups != crate::sys_ctrl::retention_lost_count() {
+            self.reinit_after_wake();
+        }
+    }
+
+    /// Set the byte order of the IVs, nonces and counters passed to the AES operations.
+    ///
+    /// The default is [`ByteOrder::BigEndian`], the native order of the AES engine. Every
+    /// field (for example the nonce and the counter of AES-CTR) is converted separately.
+    pub fn set_iv_byte_order(&mut self, order: ByteOrder) {
+        self.iv_byte_order = order;
+    }
+
+    /// Copy an IV, nonce or counter, converting it to the native order of the AES engine.
+    #[inline]
+    fn copy_iv_field(&self, dst: &mut [u8], src: &[u8]) {
+        self.iv_byte_order.copy_to_native(dst, src);
     }
 
     #[inline]
@@ -145,7 +207,7 @@ impl Crypto<'_> {
     }
 
     #[inline]
-    fn write_dma0(&mut self, data: &[u8]) {
+    fn write_dma0(&mut self, data: &[u8]) -> Result<(), CryptoError> {
         let aes = Self::aes();
 
         aes.dmac_ch0_ctrl().modify(|_, w| w.en().set_bit());
@@ -154,11 +216,11 @@ impl Crypto<'_> {
         aes.dmac_ch0_dmalength()
             .modify(|_, w| unsafe { w.dmalen().bits(data.len() as u16) });
 
-        while !aes.ctrl_int_stat().read().dma_in_done().bit_is_set() {}
+        self.wait_dma_in_done()
     }
 
     #[inline]
-    fn write_dma1(&mut self, data: &[u8]) {
+    fn write_dma1(&mut self, data: &[u8]) -> Result<(), CryptoError> {
         let aes = Self::aes();
 
         aes.dmac_ch1_ctrl().modify(|_, w| w.en().set_bit());
@@ -167,7 +229,33 @@ impl Crypto<'_> {
         aes.dmac_ch1_dmalength()
             .modify(|_, w| unsafe { w.dmalen().bits(data.len() as u16) });
 
-        while !aes.ctrl_int_stat().read().dma_in_done().bit_is_set() {}
+        self.wait_dma_in_done()
+    }
+
+    /// Wait until the DMA input transfer is done.
+    ///
+    /// A bus error (for example caused by a buffer the DMA can not access) stops the transfer,
+    /// in which case the error is cleared and returned instead of waiting forever.
+    #[inline]
+    fn wait_dma_in_done(&mut self) -> Result<(), CryptoError> {
+        let aes = Self::aes();
+
+        let deadline = Deadline::start();
+        loop {
+            let stat = aes.ctrl_int_stat().read();
+
+            if stat.dma_bus_err().bit_is_set() {
+                aes.ctrl_int_clr().write(|w| w.dma_bus_err().set_bit());
+                aes.ctrl_alg_sel().write(|w| unsafe { w.bits(0) });
+                return Err(CryptoError::DmaBusError);
+            }
+
+            if stat.dma_in_done().bit_is_set() {
+                return Ok(());
+            }
+
+            deadline.check()?;
+        }
     }
 
     /// Set the IV in the AES engine.
@@ -189,6 +277,44 @@ impl Crypto<'_> {
         }
     }
 
+    /// Read the IV/context of the last AES operation.
+    ///
+    /// The context is only saved by the modes that set the `save_context` bit: CBC
+    /// ([`Crypto::cbc_encrypt`]) leaves the last ciphertext block, which is the IV that continues
+    /// the chain, and CTR ([`Crypto::ctr_encrypt`]) leaves the nonce with the next counter value.
+    /// CCM saves its context as well, but there it holds the counter block A0, which is not
+    /// useful for chaining. Call this after the operation and before starting a new one.
+    ///
+    /// With [`ByteOrder::LittleEndian`], the complete 16-byte context is reversed.
+    ///
+    /// Returns [`CryptoError::Timeout`] when the saved context does not become ready in time
+    /// (see [`Crypto::set_timeout`]).
+    pub fn read_context(&mut self, iv_out: &mut [u8; 16]) -> Result<(), CryptoError> {
+        let aes = Self::aes();
+
+        if aes.aes_ctrl().read().save_context().bit_is_set() {
+            Self::wait_until(|| aes.aes_ctrl().read().saved_context_ready().bit_is_set())?;
+        }
+
+        // Reading the last word clears the saved_context_ready bit.
+        let iv_u32 = [
+            aes.aes_iv_0().read().bits(),
+            aes.aes_iv_1().read().bits(),
+            aes.aes_iv_2().read().bits(),
+            aes.aes_iv_3().read().bits(),
+        ];
+
+        for (c, word) in iv_out.chunks_mut(4).zip(iv_u32.iter()) {
+            c.copy_from_slice(&word.to_le_bytes());
+        }
+
+        if self.iv_byte_order == ByteOrder::LittleEndian {
+            iv_out.reverse();
+        }
+
+        Ok(())
+    }
+
     fn read_tag(&mut self, tag: &mut [u8]) {
         assert!(tag.len() == 16);
 
@@ -209,14 +335,41 @@ impl Crypto<'_> {
     }
 
     /// Load a key into AES key RAM.
-    pub fn load_key(&mut self, aes_keys: &AesKeys) {
+    ///
+    /// All keys in the key store have the same size, and changing the size erases all keys.
+    /// [`CryptoError::KeySizeMismatch`] is returned when `aes_keys` has a different size than
+    /// keys stored in other areas, which are then left untouched. Use
+    /// [`Crypto::load_key_erasing`] to load the keys anyway.
+    pub fn load_key(&mut self, aes_keys: &AesKeys) -> Result<(), CryptoError> {
+        self.store_keys(aes_keys, false).map(|_| ())
+    }
+
+    /// Load a key into AES key RAM, erasing the keys of a different size.
+    ///
+    /// Returns `true` when the keys that were in the key store were erased, because they had a
+    /// different size than `aes_keys`.
+    pub fn load_key_erasing(&mut self, aes_keys: &AesKeys) -> Result<bool, CryptoError> {
+        self.store_keys(aes_keys, true)
+    }
+
+    /// Load a key into AES key RAM, returning whether the other keys were erased.
+    fn store_keys(&mut self, aes_keys: &AesKeys, allow_erase: bool) -> Result<bool, CryptoError> {
         if Self::is_aes_in_use() {
-            return; // FIXME
+            return Err(CryptoError::AesBusy);
         }
 
         let aes = Self::aes();
 
-        self.workaround();
+        // The areas that are written, which are overwritten anyway.
+        let areas = ((0x1 << aes_keys.count) - 1) << aes_keys.start_area;
+        let size_changes = aes.key_store_size().read().key_size().bits() != aes_keys.sizes as u8;
+        let erases = size_changes && aes.key_store_written_area().read().bits() & !areas != 0;
+
+        if erases && !allow_erase {
+            return Err(CryptoError::KeySizeMismatch);
+        }
+
+        self.reinit_after_wake();
 
         // Configure the master module.
         self.set_mode(CryptoMode::StoreKeys);
@@ -224,7 +377,7 @@ impl Crypto<'_> {
         self.clear_events();
 
         // Writing to key_store_size deletes all keys.
-        if aes.key_store_size().read().key_size().bits() != aes_keys.sizes as u8 {
+        if size_changes {
             unsafe {
                 aes.key_store_size()
                     .modify(|_, w| w.key_size().bits(aes_keys.sizes as u8));
@@ -232,7 +385,6 @@ impl Crypto<'_> {
         }
 
         // Free possibly already occupied key areas.
-        let areas = ((0x1 << aes_keys.count) - 1) << aes_keys.start_area;
         unsafe { aes.key_store_written_area().write(|w| w.bits(areas)) };
         // Enable key areas to write.
         unsafe { aes.key_store_write_area().write(|w| w.bits(areas)) };
@@ -241,20 +393,20 @@ impl Crypto<'_> {
         self.set_dma_channel0_ext_addr(aes_keys.keys.as_ptr() as u32);
         self.set_dma_channel0_dmalength((aes_keys.count << 4) as u16);
 
-        while !self.is_completed() {}
+        Self::wait_until(|| self.is_completed())?;
 
         if aes.ctrl_int_stat().read().dma_bus_err().bit_is_set() {
             // Clear the error
             aes.ctrl_int_clr().write(|w| w.dma_bus_err().set_bit());
             //self.disable_master_control();
-            return; // Err(CryptoError::DmaBusError);
+            return Err(CryptoError::DmaBusError);
         }
 
         if aes.ctrl_int_stat().read().key_st_wr_err().bit_is_set() {
             // Clear the error
             aes.ctrl_int_clr().write(|w| w.key_st_wr_err().set_bit());
             //self.disable_master_control();
-            return;
+            return Err(CryptoError::KeyLoadError);
         }
 
         //self.ack_interrupt();
@@ -265,8 +417,10 @@ impl Crypto<'_> {
         //self.disable_master_control();
 
         if (aes.key_store_written_area().read().bits() & areas) != areas {
-            return;
+            return Err(CryptoError::KeyLoadError);
         }
+
+        Ok(erases)
     }
 
     fn auth_crypt(
@@ -277,11 +431,43 @@ impl Crypto<'_> {
         adata: Option<&[u8]>,
         data_in: &[u8],
         data_out: &[u8],
-    ) {
+    ) -> Result<(), CryptoError> {
+        self.auth_start(
+            ctrl,
+            key_index,
+            iv,
+            adata.map(|adata| adata.len()),
+            data_in.len(),
+        )?;
+
+        if let Some(adata) = adata {
+            self.auth_feed_adata(adata)?;
+        }
+
+        self.auth_feed(data_in, data_out)?;
+        self.auth_finish()
+    }
+
+    /// Configure the AES engine for an operation on `adata_len` bytes of added authentication
+    /// data and `data_len` bytes of payload.
+    ///
+    /// The engine then waits for the data, which is fed with [`Crypto::auth_feed_adata`] and
+    /// [`Crypto::auth_feed`] in as many parts as needed, keeping the counter and tag context
+    /// between the parts.
+    fn auth_start(
+        &mut self,
+        ctrl: impl FnOnce(&aes::RegisterBlock),
+        key_index: u32,
+        iv: Option<&[u8]>,
+        adata_len: Option<usize>,
+        data_len: usize,
+    ) -> Result<(), CryptoError> {
         if Self::is_aes_in_use() {
-            return;
+            return Err(CryptoError::AesBusy);
         }
 
+        self.reinit_if_woken();
+
         let aes = Self::aes();
         aes.ctrl_alg_sel().modify(|_, w| w.aes().set_bit());
 
@@ -289,10 +475,11 @@ impl Crypto<'_> {
             .write(|w| w.dma_in_done().set_bit().result_av().set_bit());
 
         self.set_key(key_index);
-        while !self.key_is_set() {}
+        Self::wait_until(|| self.key_is_set())?;
 
         if self.key_load_error() {
-            return;
+            aes.ctrl_int_clr().write(|w| w.key_st_rd_err().set_bit());
+            return Err(CryptoError::KeyLoadError);
         }
 
         if let Some(iv) = iv {
@@ -302,36 +489,79 @@ impl Crypto<'_> {
         ctrl(aes);
 
         aes.aes_c_length_0()
-            .write(|w| unsafe { w.bits(data_in.len() as u32) });
+            .write(|w| unsafe { w.bits(data_len as u32) });
         aes.aes_c_length_1().write(|w| unsafe { w.bits(0) });
 
-        if let Some(adata) = adata {
+        if let Some(adata_len) = adata_len {
             aes.aes_auth_length()
-                .write(|w| unsafe { w.auth_length().bits(adata.len() as u32) });
+                .write(|w| unsafe { w.auth_length().bits(adata_len as u32) });
+        }
 
-            if !adata.is_empty() {
-                self.write_dma0(adata);
+        Ok(())
+    }
 
-                if aes.ctrl_int_stat().read().dma_bus_err().bit_is_set() {
-                    return;
-                }
+    /// Feed (a part of) the added authentication data to the AES engine.
+    fn auth_feed_adata(&mut self, adata: &[u8]) -> Result<(), CryptoError> {
+        for chunk in adata.chunks(MAX_DMA_LEN) {
+            self.write_dma0(chunk)?;
 
-                aes.ctrl_int_clr().write(|w| w.dma_in_done().set_bit());
-            }
+            Self::aes()
+                .ctrl_int_clr()
+                .write(|w| w.dma_in_done().set_bit());
         }
 
-        if !data_in.is_empty() {
-            self.write_dma0(data_in);
+        Ok(())
+    }
+
+    /// Feed (a part of) the payload to the AES engine, writing the result to `data_out` when it
+    /// is not empty.
+    fn auth_feed(&mut self, data_in: &[u8], data_out: &[u8]) -> Result<(), CryptoError> {
+        // Payloads bigger than a single DMA transfer are split up. The engine is still
+        // configured for the complete length, so it waits for the next transfer without losing
+        // the counter or the tag.
+        let mut offset = 0;
+        while offset < data_in.len() {
+            let len = usize::min(MAX_DMA_LEN, data_in.len() - offset);
+
+            self.write_dma0(&data_in[offset..][..len])?;
 
             if !data_out.is_empty() {
-                self.write_dma1(data_out);
+                self.write_dma1(&data_out[offset..][..len])?;
             }
+
+            Self::aes()
+                .ctrl_int_clr()
+                .write(|w| w.dma_in_done().set_bit());
+            offset += len;
         }
 
-        while !(aes.ctrl_int_stat().read().dma_bus_err().bit_is_set()
-            || aes.ctrl_int_stat().read().key_st_rd_err().bit_is_set()
-            || aes.ctrl_int_stat().read().key_st_wr_err().bit_is_set()
-            || aes.ctrl_int_stat().read().result_av().bit_is_set())
-        {}
+        Ok(())
+    }
+
+    /// Wait until the result of the operation is available.
+    fn auth_finish(&mut self) -> Result<(), CryptoError> {
+        let aes = Self::aes();
+
+        let deadline = Deadline::start();
+        loop {
+            let stat = aes.ctrl_int_stat().read();
+
+            if stat.dma_bus_err().bit_is_set() {
+                aes.ctrl_int_clr().write(|w| w.dma_bus_err().set_bit());
+                return Err(CryptoError::DmaBusError);
+            }
+
+            if stat.key_st_rd_err().bit_is_set() || stat.key_st_wr_err().bit_is_set() {
+                aes.ctrl_int_clr()
+                    .write(|w| w.key_st_rd_err().set_bit().key_st_wr_err().set_bit());
+                return Err(CryptoError::KeyLoadError);
+            }
+
+            if stat.result_av().bit_is_set() {
+                return Ok(());
+            }
+
+            deadline.check()?;
+        }
     }
 }
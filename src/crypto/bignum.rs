@@ -10,7 +10,10 @@ use super::PkaRam;
 ///
 /// The maximum size of the big number is 64 (32-bit) words, however, the user can create it's own
 /// big number type and change the maximum size of the big number.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// Big numbers are compared by value: leading zero words are ignored, so numbers with a
+/// different size or `MAX_LEN` can be equal.
+#[derive(Debug, Eq)]
 pub struct BigNum<const MAX_LEN: usize = 64> {
     buffer: [u32; MAX_LEN],
     size: usize,
@@ -22,21 +25,131 @@ impl<const MAX_LEN: usize> core::fmt::Display for BigNum<MAX_LEN> {
     }
 }
 
+impl<const L: usize, const R: usize> PartialEq<BigNum<R>> for BigNum<L> {
+    fn eq(&self, other: &BigNum<R>) -> bool {
+        self.inner()[..self.significant_len()] == other.inner()[..other.significant_len()]
+    }
+}
+
+impl<const MAX_LEN: usize> AsRef<[u32]> for BigNum<MAX_LEN> {
+    fn as_ref(&self) -> &[u32] {
+        self.inner()
+    }
+}
+
+impl<const MAX_LEN: usize> AsMut<[u32]> for BigNum<MAX_LEN> {
+    fn as_mut(&mut self) -> &mut [u32] {
+        self.inner_mut()
+    }
+}
+
 impl<const MAX_LEN: usize> BigNum<MAX_LEN> {
     /// Create a new big number, with `size` amount of words to use from the buffer.
+    ///
+    /// # Panics
+    /// Panics when `size` is bigger than `MAX_LEN`, see [`BigNum::try_new`].
     pub fn new(size: usize) -> Self {
+        assert!(size <= MAX_LEN);
         Self {
             buffer: [0u32; MAX_LEN],
             size,
         }
     }
 
+    /// Create a new big number, with `size` amount of words to use from the buffer.
+    ///
+    /// Returns [`CryptoError::CapacityExceeded`] when `size` is bigger than `MAX_LEN`.
+    pub fn try_new(size: usize) -> Result<Self, CryptoError> {
+        if size > MAX_LEN {
+            return Err(CryptoError::CapacityExceeded);
+        }
+
+        Ok(Self {
+            buffer: [0u32; MAX_LEN],
+            size,
+        })
+    }
+
+    /// Create a big number from words, least significant word first, e.g. a scalar or a
+    /// coordinate of an [`EcPoint`](super::ecc::EcPoint).
+    ///
+    /// Returns [`CryptoError::CapacityExceeded`] when `words` is longer than `MAX_LEN`.
+    pub fn from_slice(words: &[u32]) -> Result<Self, CryptoError> {
+        let mut tmp = Self::try_new(words.len())?;
+        tmp.inner_mut().copy_from_slice(words);
+        Ok(tmp)
+    }
+
+    /// Create a big number with value 0, with `size` amount of words to use from the buffer.
+    ///
+    /// # Panics
+    /// Panics when `size` is bigger than `MAX_LEN`.
+    pub fn zero(size: usize) -> Self {
+        Self::new(size)
+    }
+
+    /// Create a big number with value 1, with `size` amount of words to use from the buffer.
+    ///
+    /// # Panics
+    /// Panics when `size` is 0 or bigger than `MAX_LEN`.
+    pub fn one(size: usize) -> Self {
+        assert!(size > 0);
+        let mut tmp = Self::new(size);
+        tmp.buffer[0] = 1;
+        tmp
+    }
+
+    /// Check if the value is 0.
+    pub fn is_zero(&self) -> bool {
+        self.significant_len() == 0
+    }
+
+    /// Check if the value is 1.
+    pub fn is_one(&self) -> bool {
+        self.significant_len() == 1 && self.buffer[0] == 1
+    }
+
     /// Set the amount of words to use from the buffer.
+    ///
+    /// # Panics
+    /// Panics when `size` is bigger than `MAX_LEN`, see [`BigNum::try_set_size`].
     pub fn set_size(&mut self, size: usize) {
         assert!(size <= MAX_LEN);
         self.size = size;
     }
 
+    /// Set the amount of words to use from the buffer.
+    ///
+    /// Returns [`CryptoError::CapacityExceeded`] when `size` is bigger than `MAX_LEN`.
+    pub fn try_set_size(&mut self, size: usize) -> Result<(), CryptoError> {
+        if size > MAX_LEN {
+            return Err(CryptoError::CapacityExceeded);
+        }
+
+        self.size = size;
+        Ok(())
+    }
+
+    /// Grow the amount of words to use from the buffer to `size`, padding the value with zero
+    /// words.
+    ///
+    /// The results of the operations only use the words they need, while the ECC operations
+    /// need every coordinate to be as long as the curve. Nothing changes when the big number
+    /// already has `size` or more words. Returns [`CryptoError::CapacityExceeded`] when `size`
+    /// is bigger than `MAX_LEN`.
+    pub fn zero_extend(&mut self, size: usize) -> Result<(), CryptoError> {
+        if size > MAX_LEN {
+            return Err(CryptoError::CapacityExceeded);
+        }
+
+        if size > self.size {
+            self.buffer[self.size..size].fill(0);
+            self.size = size;
+        }
+
+        Ok(())
+    }
+
     /// Return a slice to the buffer.
     pub fn inner(&self) -> &[u32] {
         &self.buffer[..self.size]
@@ -49,7 +162,7 @@ impl<const MAX_LEN: usize> BigNum<MAX_LEN> {
 
     /// Addition of two big numbers.
     pub fn add<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(self.size.max(rhs.size) + 1);
+        let mut tmp = BigNum::try_new(self.size.max(rhs.size) + 1)?;
         let len = Crypto::add(self.inner(), rhs.inner(), tmp.inner_mut())?;
         tmp.set_size(len);
         Ok(tmp)
@@ -57,7 +170,7 @@ impl<const MAX_LEN: usize> BigNum<MAX_LEN> {
 
     /// Subtraction of two big numbers.
     pub fn sub<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(self.size.max(rhs.size));
+        let mut tmp = BigNum::try_new(self.size.max(rhs.size))?;
         let len = Crypto::sub(self.inner(), rhs.inner(), tmp.inner_mut())?;
         tmp.set_size(len);
         Ok(tmp)
@@ -70,7 +183,7 @@ impl<const MAX_LEN: usize> BigNum<MAX_LEN> {
         c: &BigNum<MAX_LEN>,
         b: &BigNum<MAX_LEN>,
     ) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(self.size);
+        let mut tmp = BigNum::try_new(self.size)?;
         let len = Crypto::add_sub(self.inner(), c.inner(), b.inner(), tmp.inner_mut())?;
         tmp.set_size(len);
         Ok(tmp)
@@ -78,48 +191,135 @@ impl<const MAX_LEN: usize> BigNum<MAX_LEN> {
 
     /// Multiplication of two big numbers.
     pub fn mul<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(self.size + rhs.size + 6);
+        let mut tmp = BigNum::try_new(self.size + rhs.size + 6)?;
         let len = Crypto::mul(self.inner(), rhs.inner(), tmp.inner_mut())?;
         tmp.set_size(len);
         Ok(tmp)
     }
 
+    /// Modular multiplication of two big numbers.
+    ///
+    /// (A * B) mod N, where `self` is A.
+    pub fn mul_mod<const L: usize, const M: usize>(
+        &self,
+        rhs: &BigNum<L>,
+        modulus: &BigNum<M>,
+    ) -> Result<BigNum<MAX_LEN>, CryptoError> {
+        let mut tmp = BigNum::try_new(modulus.size + 1)?;
+        let len = Crypto::mulmod(self.inner(), rhs.inner(), modulus.inner(), tmp.inner_mut())?;
+        tmp.set_size(len);
+        Ok(tmp)
+    }
+
     /// Division of two big numbers.
-    pub fn div<const L: usize>(&self, rhs: &BigNum<L>) -> BigNum<MAX_LEN> {
-        let mut tmp = BigNum::new(self.size + rhs.size + 6);
+    pub fn div<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
+        let mut tmp = BigNum::try_new(self.size + rhs.size + 6)?;
         Crypto::div(self.inner(), rhs.inner(), tmp.inner_mut());
-        tmp
+        Ok(tmp)
     }
 
     /// Modulus of two big numbers.
     pub fn modulo<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(rhs.size + 2);
+        let mut tmp = BigNum::try_new(rhs.size + 2)?;
         let len = Crypto::modulo(self.inner(), rhs.inner(), tmp.inner_mut())?;
         tmp.set_size(len);
         Ok(tmp)
     }
 
     /// Inverse modulus of two big numbers.
+    ///
+    /// Returns [`CryptoError::NoInverse`] when `self` has no inverse modulo `rhs` (they are not
+    /// coprime). This is not a hardware failure: in ECDSA for example, it means that the
+    /// signature should be retried with a new nonce.
     pub fn inv_mod<const L: usize>(&self, rhs: &BigNum<L>) -> Result<BigNum<MAX_LEN>, CryptoError> {
-        let mut tmp = BigNum::new(rhs.size + 1);
-        Crypto::inv_modulo(self.inner(), rhs.inner(), tmp.inner_mut())?;
+        let mut tmp = BigNum::try_new(rhs.size + 1)?;
+        Crypto::inv_modulo(self.inner(), rhs.inner(), tmp.inner_mut()).map_err(|e| match e {
+            CryptoError::NoSolution => CryptoError::NoInverse,
+            e => e,
+        })?;
         Ok(tmp)
     }
 
     /// Exponentiation with big numbers.
     ///
     /// C^A mod B -> D, where `self` is A.
-    pub fn exp<const L: usize>(&self, modulus: &BigNum<L>, base: &BigNum<L>) -> BigNum<MAX_LEN> {
-        // TODO: calculate the correct maximum length.
-        let mut tmp = BigNum::new(MAX_LEN);
-        Crypto::exp(self.inner(), modulus.inner(), base.inner(), tmp.inner_mut());
-        tmp
+    pub fn exp<const L: usize>(
+        &self,
+        modulus: &BigNum<L>,
+        base: &BigNum<L>,
+    ) -> Result<BigNum<MAX_LEN>, CryptoError> {
+        // The result is smaller than the modulus.
+        let mut tmp = BigNum::try_new(modulus.size)?;
+        let len = Crypto::exp(self.inner(), modulus.inner(), base.inner(), tmp.inner_mut())?;
+        tmp.set_size(len);
+        Ok(tmp)
     }
 
     /// Comparision of two big numbers.
     pub fn compare<const L: usize>(&self, rhs: &BigNum<L>) -> Option<Ordering> {
         Crypto::cmp(self.inner(), rhs.inner())
     }
+
+    /// Shift a big number `bits` to the left.
+    ///
+    /// The result grows with the shifted out words, leading zero words are not kept. Returns
+    /// [`CryptoError::CapacityExceeded`] when the result does not fit in `MAX_LEN` words.
+    pub fn shl(&self, bits: usize) -> Result<BigNum<MAX_LEN>, CryptoError> {
+        let words = bits / 32;
+        let bits = bits % 32;
+        let len = self.significant_len();
+
+        if len == 0 {
+            return BigNum::try_new(0);
+        }
+
+        // An extra word is needed when bits are shifted out of the most significant word.
+        let extra = (bits != 0 && self.buffer[len - 1].leading_zeros() < bits as u32) as usize;
+        let mut tmp = BigNum::try_new(len + words + extra)?;
+
+        for (i, w) in self.buffer[..len].iter().enumerate() {
+            tmp.buffer[i + words] |= w << bits;
+            if bits != 0 && i + words + 1 < tmp.size {
+                tmp.buffer[i + words + 1] |= w >> (32 - bits);
+            }
+        }
+
+        Ok(tmp)
+    }
+
+    /// Shift a big number `bits` to the right.
+    ///
+    /// The shifted out bits are discarded, leading zero words are not kept.
+    pub fn shr(&self, bits: usize) -> Result<BigNum<MAX_LEN>, CryptoError> {
+        let words = bits / 32;
+        let bits = bits % 32;
+        let len = self.significant_len();
+
+        if words >= len {
+            return BigNum::try_new(0);
+        }
+
+        let mut tmp = BigNum::try_new(len - words)?;
+
+        for i in 0..tmp.size {
+            let mut w = self.buffer[i + words] >> bits;
+            if bits != 0 && i + words + 1 < len {
+                w |= self.buffer[i + words + 1] << (32 - bits);
+            }
+            tmp.buffer[i] = w;
+        }
+
+        tmp.size = tmp.significant_len();
+        Ok(tmp)
+    }
+
+    /// Return the amount of words without the leading zero words.
+    fn significant_len(&self) -> usize {
+        self.inner()
+            .iter()
+            .rposition(|w| *w != 0)
+            .map_or(0, |i| i + 1)
+    }
 }
 
 impl Crypto<'_> {
@@ -157,21 +357,10 @@ impl Crypto<'_> {
             .write(|w| unsafe { w.blength().bits(length as u16) });
     }
 
-    /// Addition of two bignums.
-    pub fn add(
-        num1: impl AsRef<[u32]>,
-        num2: impl AsRef<[u32]>,
-        result: &mut (impl AsMut<[u32]> + ?Sized),
-    ) -> Result<usize, CryptoError> {
-        let num1 = num1.as_ref();
-        let num2 = num2.as_ref();
-        let result = result.as_mut();
-
-        if Self::is_pka_in_use() {
-            return Err(CryptoError::PkaBusy);
-        }
-
-        let pka = Self::pka();
+    /// Write the A and B vectors to the PKA RAM, followed by the address of the C vector.
+    ///
+    /// Returns the start of the C vector, in words.
+    fn load_a_b(num1: &[u32], num2: &[u32]) -> usize {
         let mut offset: usize = 0;
 
         // Save the address of the A vector.
@@ -184,26 +373,71 @@ impl Crypto<'_> {
 
         // Save the address of the C vector.
         Self::set_c_ptr(offset >> 2);
-        let result_start = offset >> 2;
 
         Self::set_a_length(num1.len());
         Self::set_b_length(num2.len());
 
-        // Start the add operation.
-        pka.function().write(|w| w.add().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        offset >> 2
+    }
 
-        let result_end = pka.msw().read().msw_address().bits() as usize;
+    /// Read the C vector of a finished operation, returning its length.
+    fn read_c_result(result: &mut [u32], result_start: usize) -> usize {
+        let pka = Self::pka();
 
+        let result_end = pka.msw().read().msw_address().bits() as usize;
         if pka.msw().read().result_is_zero().bit_is_set() {
             result.fill_with(|| 0);
-            return Ok(0);
+            return 0;
         }
 
         let len = result_end - result_start + 1;
 
         PkaRam::read_slice(&mut result[..len], result_start << 2);
-        Ok(len)
+        len
+    }
+
+    /// Addition of two bignums.
+    pub fn add(
+        num1: impl AsRef<[u32]>,
+        num2: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
+
+        let result_start = Self::load_a_b(num1.as_ref(), num2.as_ref());
+
+        // Start the add operation.
+        Self::pka()
+            .function()
+            .write(|w| w.add().set_bit().run().set_bit());
+        Self::wait_pka_done()?;
+
+        Ok(Self::read_c_result(result.as_mut(), result_start))
+    }
+
+    /// Addition of two bignums, waiting for the PKA interrupt instead of busy waiting.
+    ///
+    /// See [`Crypto::add`].
+    pub async fn add_async(
+        num1: impl AsRef<[u32]>,
+        num2: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
+
+        let result_start = Self::load_a_b(num1.as_ref(), num2.as_ref());
+
+        // Start the add operation.
+        Self::pka()
+            .function()
+            .write(|w| w.add().set_bit().run().set_bit());
+        Self::wait_pka().await;
+
+        Ok(Self::read_c_result(result.as_mut(), result_start))
     }
 
     /// Subtraction of two bignums.
@@ -243,7 +477,7 @@ impl Crypto<'_> {
         // Start the subtract operation.
         pka.function()
             .write(|w| w.subtract().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done()?;
 
         let result_end = pka.msw().read().msw_address().bits() as usize;
 
@@ -299,7 +533,7 @@ impl Crypto<'_> {
 
         // Start the subtract operation.
         pka.function().write(|w| w.addsub().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done()?;
 
         let result_end = pka.msw().read().msw_address().bits() as usize;
 
@@ -322,9 +556,61 @@ impl Crypto<'_> {
         num1: impl AsRef<[u32]>,
         num2: impl AsRef<[u32]>,
         result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
+
+        let result_start = Self::load_a_b(num1.as_ref(), num2.as_ref());
+
+        // Start the multiplaction operation.
+        Self::pka()
+            .function()
+            .write(|w| w.multiply().set_bit().run().set_bit());
+        Self::wait_pka_done()?;
+
+        Ok(Self::read_c_result(result.as_mut(), result_start))
+    }
+
+    /// Multiplication of two bignums, waiting for the PKA interrupt instead of busy waiting.
+    ///
+    /// See [`Crypto::mul`].
+    pub async fn mul_async(
+        num1: impl AsRef<[u32]>,
+        num2: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
+
+        let result_start = Self::load_a_b(num1.as_ref(), num2.as_ref());
+
+        // Start the multiplaction operation.
+        Self::pka()
+            .function()
+            .write(|w| w.multiply().set_bit().run().set_bit());
+        Self::wait_pka().await;
+
+        Ok(Self::read_c_result(result.as_mut(), result_start))
+    }
+
+    /// Modular multiplication of two bignums.
+    ///
+    /// (num1 * num2) mod modulus -> result
+    ///
+    /// The PKA has no combined multiply-modulo operation. The product is therefore kept in PKA
+    /// RAM and directly used as the input of the modulo operation, which avoids reading it back
+    /// and writing it again. The length of `result` should be at least modulus + 1.
+    pub fn mulmod(
+        num1: impl AsRef<[u32]>,
+        num2: impl AsRef<[u32]>,
+        modulus: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
     ) -> Result<usize, CryptoError> {
         let num1 = num1.as_ref();
         let num2 = num2.as_ref();
+        let modulus = modulus.as_ref();
         let result = result.as_mut();
 
         if Self::is_pka_in_use() {
@@ -342,28 +628,52 @@ impl Crypto<'_> {
         Self::set_b_ptr(offset >> 2);
         offset += PkaRam::write_slice(num2, offset);
 
-        // Save the address of the C vector.
+        // Save the address of the C vector, which holds the product.
         Self::set_c_ptr(offset >> 2);
-        let result_start = offset >> 2;
+        let product_start = offset >> 2;
 
         Self::set_a_length(num1.len());
         Self::set_b_length(num2.len());
 
-        // Start the multiplaction operation.
+        // Start the multiplication operation.
         pka.function()
             .write(|w| w.multiply().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done()?;
 
-        let result_end = pka.msw().read().msw_address().bits() as usize;
         if pka.msw().read().result_is_zero().bit_is_set() {
             result.fill_with(|| 0);
-            return Ok(0);
+            return Ok(modulus.len() + 1);
         }
 
-        let len = result_end - result_start + 1;
+        let product_len = pka.msw().read().msw_address().bits() as usize - product_start + 1;
 
-        PkaRam::read_slice(&mut result[..len], result_start << 2);
-        Ok(len)
+        // Reserve the maximum size of the product, including the extra words used by the PKA.
+        offset += ((num1.len() + num2.len() + 6) * 4).div_ceil(8) * 8;
+
+        // The product is the A vector of the modulo operation.
+        Self::set_a_ptr(product_start);
+
+        // Save the address of the B vector.
+        Self::set_b_ptr(offset >> 2);
+        offset += PkaRam::write_slice(modulus, offset);
+
+        // Save the address of the C vector.
+        Self::set_c_ptr(offset >> 2);
+
+        Self::set_a_length(product_len);
+        Self::set_b_length(modulus.len());
+
+        // Start the modulo operation.
+        pka.function().write(|w| w.modulo().set_bit().run().set_bit());
+        Self::wait_pka_done()?;
+
+        if pka.msw().read().result_is_zero().bit_is_set() {
+            result.fill_with(|| 0);
+            return Ok(modulus.len() + 1);
+        }
+
+        PkaRam::read_slice(&mut result[..modulus.len() + 1], offset);
+        Ok(modulus.len() + 1)
     }
 
     /// Division of two bignums.
@@ -378,42 +688,64 @@ impl Crypto<'_> {
         num2: impl AsRef<[u32]>,
         result: &mut (impl AsMut<[u32]> + ?Sized),
     ) -> Result<usize, CryptoError> {
-        let num1 = num1.as_ref();
-        let num2 = num2.as_ref();
-        let result = result.as_mut();
-
         if Self::is_pka_in_use() {
             return Err(CryptoError::PkaBusy);
         }
 
-        let pka = Self::pka();
-        let mut offset: usize = 0;
-
-        // Save the address of the A vector.
-        Self::set_a_ptr(offset);
-        offset += PkaRam::write_slice(num1, offset);
+        let num2 = num2.as_ref();
+        let result_start = Self::load_a_b(num1.as_ref(), num2);
 
-        // Save the address of the B vector.
-        Self::set_b_ptr(offset >> 2);
-        offset += PkaRam::write_slice(num2, offset);
+        // Start the modulo operation.
+        Self::pka()
+            .function()
+            .write(|w| w.modulo().set_bit().run().set_bit());
+        Self::wait_pka_done()?;
+
+        Ok(Self::read_modulo_result(
+            result.as_mut(),
+            num2.len(),
+            result_start,
+        ))
+    }
 
-        // Save the address of the C vector.
-        Self::set_c_ptr(offset >> 2);
+    /// Modulo of a bignums, waiting for the PKA interrupt instead of busy waiting.
+    ///
+    /// See [`Crypto::modulo`].
+    pub async fn modulo_async(
+        num1: impl AsRef<[u32]>,
+        num2: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
 
-        Self::set_a_length(num1.len());
-        Self::set_b_length(num2.len());
+        let num2 = num2.as_ref();
+        let result_start = Self::load_a_b(num1.as_ref(), num2);
 
         // Start the modulo operation.
-        pka.function().write(|w| w.modulo().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        Self::pka()
+            .function()
+            .write(|w| w.modulo().set_bit().run().set_bit());
+        Self::wait_pka().await;
+
+        Ok(Self::read_modulo_result(
+            result.as_mut(),
+            num2.len(),
+            result_start,
+        ))
+    }
 
-        if pka.msw().read().result_is_zero().bit_is_set() {
+    /// Read the remainder of a finished modulo operation, which is one word longer than the
+    /// modulus.
+    fn read_modulo_result(result: &mut [u32], modulus_len: usize, result_start: usize) -> usize {
+        if Self::pka().msw().read().result_is_zero().bit_is_set() {
             result.fill_with(|| 0);
-            return Ok(num2.len() + 1);
+            return modulus_len + 1;
         }
 
-        PkaRam::read_slice(&mut result[..num2.len() + 1], offset);
-        Ok(num2.len() + 1)
+        PkaRam::read_slice(&mut result[..modulus_len + 1], result_start << 2);
+        modulus_len + 1
     }
 
     /// Inverse modulo of a bignums.
@@ -442,7 +774,7 @@ impl Crypto<'_> {
         // Start the inverse module operation
         pka.function()
             .write(|w| unsafe { w.sequencer_operations().bits(0b111).run().set_bit() });
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done()?;
 
         let status = pka.shift().read().bits();
         match status {
@@ -458,22 +790,65 @@ impl Crypto<'_> {
 
     /// Exponentiation of a bignums.
     /// c^a mod b -> d
+    ///
+    /// The result is smaller than the modulus, so `result` should be as long as `modulus`.
+    /// Returns the length of the result, which is 0 when the result is zero. Returns
+    /// [`CryptoError::CapacityExceeded`] when the vectors do not fit in the PKA RAM.
     pub fn exp(
         exponent: impl AsRef<[u32]>,
         modulus: impl AsRef<[u32]>,
         base: impl AsRef<[u32]>,
         result: &mut (impl AsMut<[u32]> + ?Sized),
-    ) {
-        let exponent = exponent.as_ref();
-        let modulus = modulus.as_ref();
-        let base = base.as_ref();
-        let result = result.as_mut();
+    ) -> Result<usize, CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
 
+        let offset = Self::load_exp(exponent.as_ref(), modulus.as_ref(), base.as_ref())?;
+
+        // Start the exp operation.
+        Self::pka()
+            .function()
+            .write(|w| unsafe { w.sequencer_operations().bits(0b010).run().set_bit() });
+        Self::wait_pka_done()?;
+
+        Ok(Self::read_exp_result(result.as_mut(), offset))
+    }
+
+    /// Exponentiation of a bignums, waiting for the PKA interrupt instead of busy waiting.
+    ///
+    /// See [`Crypto::exp`].
+    pub async fn exp_async(
+        exponent: impl AsRef<[u32]>,
+        modulus: impl AsRef<[u32]>,
+        base: impl AsRef<[u32]>,
+        result: &mut (impl AsMut<[u32]> + ?Sized),
+    ) -> Result<usize, CryptoError> {
         if Self::is_pka_in_use() {
-            return;
+            return Err(CryptoError::PkaBusy);
         }
 
-        let pka = Self::pka();
+        let offset = Self::load_exp(exponent.as_ref(), modulus.as_ref(), base.as_ref())?;
+
+        // Start the exp operation.
+        Self::pka()
+            .function()
+            .write(|w| unsafe { w.sequencer_operations().bits(0b010).run().set_bit() });
+        Self::wait_pka().await;
+
+        Ok(Self::read_exp_result(result.as_mut(), offset))
+    }
+
+    /// Write the vectors of the exponentiation to the PKA RAM.
+    ///
+    /// Returns the offset of the D vector.
+    fn load_exp(exponent: &[u32], modulus: &[u32], base: &[u32]) -> Result<usize, CryptoError> {
+        // Every vector starts 8-byte aligned, and the modulus is followed by two zero words.
+        let aligned = |len: usize| (len + len % 2) * 4;
+        let size = aligned(exponent.len()) + aligned(modulus.len() + 2) + aligned(base.len());
+        if size > PkaRam::PKA_RAM_SIZE {
+            return Err(CryptoError::CapacityExceeded);
+        }
 
         let mut offset: usize = 0;
 
@@ -484,6 +859,7 @@ impl Crypto<'_> {
         // Save the address of the B vector.
         Self::set_b_ptr(offset >> 2);
         offset += PkaRam::write_slice(modulus, offset);
+        offset += PkaRam::write_slice(&[0, 0], offset);
 
         // Save the address of the C vector.
         Self::set_c_ptr(offset >> 2);
@@ -496,21 +872,25 @@ impl Crypto<'_> {
         Self::set_a_length(exponent.len());
         Self::set_b_length(modulus.len());
 
-        // Start the exp operation.
-        pka.function()
-            .write(|w| unsafe { w.sequencer_operations().bits(0b010).run().set_bit() });
-        while Self::is_pka_in_use() {}
+        Ok(offset)
+    }
+
+    /// Read the D vector of a finished exponentiation, returning its length.
+    fn read_exp_result(result: &mut [u32], offset: usize) -> usize {
+        let pka = Self::pka();
 
         let msw_val = pka.msw().read().msw_address().bits() as usize;
         if msw_val == 0 || pka.msw().read().result_is_zero().bit_is_set() {
-            return;
+            result.fill_with(|| 0);
+            return 0;
         }
 
-        let len1 = msw_val + 1;
-        let len2 = pka.dptr().read().bits() as usize;
-        let len = len1 - len2;
+        // The MSW register holds the word address of the most significant word of the result,
+        // the result starts at the word address of the D vector.
+        let len = msw_val + 1 - (offset >> 2);
 
         PkaRam::read_slice(&mut result[..len], offset);
+        len
     }
 
     /// Comparison of two bignums.
@@ -538,7 +918,7 @@ impl Crypto<'_> {
         // Start the comparison operation.
         pka.function()
             .write(|w| w.compare().set_bit().run().set_bit());
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done().ok()?;
 
         let compare = Crypto::pka().compare().read();
         if compare.a_equals_b().bit_is_set() {
@@ -1,3 +1,4 @@
+use super::bignum::BigNum;
 use super::Crypto;
 use super::CryptoError;
 use super::PkaRam;
@@ -80,6 +81,42 @@ pub struct EcPoint<'p> {
     pub y: &'p [u32],
 }
 
+impl<'p> EcPoint<'p> {
+    /// Create a point from the coordinates as big numbers.
+    ///
+    /// Both coordinates must have at least the size of the curve, use
+    /// [`BigNum::zero_extend`] for the result of a big number operation.
+    pub fn from_bignums<const X: usize, const Y: usize>(
+        x: &'p BigNum<X>,
+        y: &'p BigNum<Y>,
+    ) -> Self {
+        Self {
+            x: x.inner(),
+            y: y.inner(),
+        }
+    }
+
+    /// Create a point from the result of [`Crypto::ecc_mul`] or [`Crypto::ecc_add`], which
+    /// holds the x coordinate followed by the y coordinate, of `curve.size` words each.
+    pub fn from_result(curve: &EccCurveInfo, result: &'p [u32]) -> Self {
+        Self {
+            x: &result[..curve.size],
+            y: &result[curve.size..][..curve.size],
+        }
+    }
+
+    /// Copy the x coordinate into a big number, e.g. to reduce it modulo the order of the
+    /// curve.
+    pub fn x_bignum<const N: usize>(&self) -> Result<BigNum<N>, CryptoError> {
+        BigNum::from_slice(self.x)
+    }
+
+    /// Copy the y coordinate into a big number.
+    pub fn y_bignum<const N: usize>(&self) -> Result<BigNum<N>, CryptoError> {
+        BigNum::from_slice(self.y)
+    }
+}
+
 impl Crypto<'_> {
     pub fn ecc_mul(
         &mut self,
@@ -92,6 +129,48 @@ impl Crypto<'_> {
             return Err(CryptoError::PkaBusy);
         }
 
+        let offset = Self::load_ecc_mul(curve, scalar, point);
+
+        // Start the multiplication operation.
+        //pka.function.write(|w| unsafe { w.bits(0x0000d000) });
+        Self::pka()
+            .function()
+            .write(|w| unsafe { w.sequencer_operations().bits(0b101).run().set_bit() });
+        Self::wait_pka_done()?;
+
+        Self::read_ecc_mul_result(result, offset)
+    }
+
+    /// Multiply `point` with `scalar`, waiting for the PKA interrupt instead of busy waiting.
+    ///
+    /// A point multiplication takes several milliseconds, during which other tasks of the
+    /// executor can run. See [`Crypto::ecc_mul`].
+    pub async fn ecc_mul_async(
+        &mut self,
+        curve: &EccCurveInfo<'_>,
+        scalar: &[u32],
+        point: &EcPoint<'_>,
+        result: &mut [u32],
+    ) -> Result<(), CryptoError> {
+        if Self::is_pka_in_use() {
+            return Err(CryptoError::PkaBusy);
+        }
+
+        let offset = Self::load_ecc_mul(curve, scalar, point);
+
+        // Start the multiplication operation.
+        Self::pka()
+            .function()
+            .write(|w| unsafe { w.sequencer_operations().bits(0b101).run().set_bit() });
+        Self::wait_pka().await;
+
+        Self::read_ecc_mul_result(result, offset)
+    }
+
+    /// Write the vectors of the ECC multiplication to the PKA RAM.
+    ///
+    /// Returns the offset of the D vector.
+    fn load_ecc_mul(curve: &EccCurveInfo, scalar: &[u32], point: &EcPoint) -> usize {
         let pka = Self::pka();
 
         let extra_buf: u8 = (2 + curve.size as u8 % 2) * 4;
@@ -123,11 +202,12 @@ impl Crypto<'_> {
         // Set the size of the B vector.
         pka.blength().write(|w| unsafe { w.bits(curve.size as u32) });
 
-        // Start the multiplication operation.
-        //pka.function.write(|w| unsafe { w.bits(0x0000d000) });
-        pka.function()
-            .write(|w| unsafe { w.sequencer_operations().bits(0b101).run().set_bit() });
-        while Self::is_pka_in_use() {}
+        offset
+    }
+
+    /// Read the x and y coordinates of a finished ECC multiplication from the D vector.
+    fn read_ecc_mul_result(result: &mut [u32], mut offset: usize) -> Result<(), CryptoError> {
+        let pka = Self::pka();
 
         if pka.shift().read().bits() != 0x0 && pka.shift().read().bits() != 0x7 {
             return Err(CryptoError::PkaFailure);
@@ -149,6 +229,53 @@ impl Crypto<'_> {
         Ok(())
     }
 
+    /// Multiply the base point of the curve with `scalar`, e.g. to derive a public key from a
+    /// private key.
+    ///
+    /// The PKA of the CC2538 has no dedicated operation for multiplying the generator point,
+    /// so this uses the same ECC multiplication as [`Crypto::ecc_mul`] with the base point of
+    /// `curve`. The scalar must be in `[1, order)`, otherwise [`CryptoError::InvalidScalar`] is
+    /// returned.
+    pub fn ecc_mul_gen(
+        &mut self,
+        curve: &EccCurveInfo,
+        scalar: &[u32],
+        result: &mut [u32],
+    ) -> Result<(), CryptoError> {
+        if !Self::is_scalar_in_range(scalar, curve.order) {
+            return Err(CryptoError::InvalidScalar);
+        }
+
+        let generator = EcPoint {
+            x: curve.bp_x,
+            y: curve.bp_y,
+        };
+
+        self.ecc_mul(curve, scalar, &generator, result)
+    }
+
+    /// Check that `scalar` is in `[1, order)`.
+    ///
+    /// Both numbers are stored with the least significant word first.
+    fn is_scalar_in_range(scalar: &[u32], order: &[u32]) -> bool {
+        let len = scalar.len().max(order.len());
+        let word = |num: &[u32], i: usize| num.get(i).copied().unwrap_or(0);
+
+        if scalar.iter().all(|w| *w == 0) {
+            return false;
+        }
+
+        for i in (0..len).rev() {
+            let (s, o) = (word(scalar, i), word(order, i));
+            if s != o {
+                return s < o;
+            }
+        }
+
+        // The scalar is equal to the order.
+        false
+    }
+
     pub fn ecc_add(
         &mut self,
         curve: &EccCurveInfo,
@@ -195,7 +322,7 @@ impl Crypto<'_> {
         //pka.function.write(|w| unsafe { w.bits(0x0000b000) });
         pka.function()
             .write(|w| unsafe { w.sequencer_operations().bits(0b011).run().set_bit() });
-        while Self::is_pka_in_use() {}
+        Self::wait_pka_done()?;
 
         if pka.shift().read().bits() != 0x0 && pka.shift().read().bits() != 0x7 {
             return Err(CryptoError::PkaFailure);
@@ -1,10 +1,17 @@
 use core::convert::TryInto;
 use core::default;
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
 
+use cc2538_pac::Interrupt as interrupt;
 use cc2538_pac::{aes, pka, Aes, Pka};
+use cortex_m::peripheral::{DWT, NVIC};
 use rtt_target::rprintln;
 
+use crate::interrupt::WakerCell;
+
 pub mod aes_engine;
 use aes_engine::*;
 
@@ -49,11 +56,65 @@ pub enum CryptoError {
     ResultIsZero,
     PkaFailure,
     NoSolution,
+    /// The number has no inverse for the modulus, since they are not coprime.
+    NoInverse,
+    InvalidScalar,
+    /// The result does not fit in the big number.
+    CapacityExceeded,
+    /// The DMA of the AES engine could not access a buffer.
+    DmaBusError,
+    /// The key could not be loaded from the key store into the AES engine.
+    KeyLoadError,
+    /// Loading the keys would erase the keys in the key store, since they have a different
+    /// size. See [`Crypto::load_key_erasing`].
+    KeySizeMismatch,
+    /// The AES engine or the PKA did not finish within the timeout, see
+    /// [`Crypto::set_timeout`].
+    Timeout,
+    /// A part of a multi-part operation does not fit the lengths the operation was started
+    /// with, or is not a multiple of the AES block size while it is not the last part.
+    InvalidLength,
+}
+
+/// The default timeout of the crypto operations in CPU cycles: 2 s at 32 MHz, which leaves
+/// plenty of margin for a P-256 point multiplication.
+const DEFAULT_TIMEOUT_CYCLES: u32 = 64_000_000;
+
+/// The timeout of the crypto operations in CPU cycles, 0 when waiting forever.
+static TIMEOUT_CYCLES: AtomicU32 = AtomicU32::new(DEFAULT_TIMEOUT_CYCLES);
+
+/// The end of a wait for the AES engine or the PKA, measured with the DWT cycle counter.
+pub(crate) struct Deadline {
+    start: u32,
+    cycles: u32,
+}
+
+impl Deadline {
+    /// Start waiting, with the timeout set with [`Crypto::set_timeout`].
+    pub(crate) fn start() -> Self {
+        Self {
+            start: DWT::cycle_count(),
+            cycles: TIMEOUT_CYCLES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Return [`CryptoError::Timeout`] when the timeout expired.
+    pub(crate) fn check(&self) -> Result<(), CryptoError> {
+        if self.cycles != 0 && DWT::cycle_count().wrapping_sub(self.start) > self.cycles {
+            Err(CryptoError::Timeout)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 pub struct Crypto<'p> {
-    _aes: PhantomData<&'p mut Aes>,
-    _pka: PhantomData<&'p mut Pka>,
+    aes: &'p mut Aes,
+    pka: &'p mut Pka,
+    /// The wake ups from PM2/PM3 seen when the AES registers were last initialised.
+    wakeups: u32,
+    /// The byte order of the IVs, nonces and counters passed to the AES engine.
+    iv_byte_order: aes_engine::ByteOrder,
 }
 
 impl Crypto<'_> {
@@ -69,6 +130,37 @@ impl Crypto<'_> {
         unsafe { &*Pka::ptr() }
     }
 
+    /// Set the maximum time to wait for an operation of the AES engine or the PKA.
+    ///
+    /// When the engine does not finish in time, e.g. because of a misconfigured clock, the
+    /// operation returns [`CryptoError::Timeout`] instead of hanging. The engine is not
+    /// stopped, so it should be reset before it is used again. The default is 2 s at 32 MHz.
+    ///
+    /// The timeout is measured with the DWT cycle counter, which must be enabled (see
+    /// [`crate::time::MonoTimer::new`]), otherwise the operations never time out. A zero
+    /// duration disables the timeout, a non-zero duration is at least one cycle.
+    pub fn set_timeout(&mut self, duration: Duration, clock: &crate::sys_ctrl::ClockConfig) {
+        let mut cycles = duration.as_nanos() * clock.sys_freq() as u128 / 1_000_000_000;
+        if !duration.is_zero() {
+            cycles = cycles.max(1);
+        }
+        TIMEOUT_CYCLES.store(cycles.min(u32::MAX as u128) as u32, Ordering::Relaxed);
+    }
+
+    /// Wait until `done` returns `true`, or until the timeout expires.
+    pub(crate) fn wait_until(mut done: impl FnMut() -> bool) -> Result<(), CryptoError> {
+        let deadline = Deadline::start();
+        while !done() {
+            deadline.check()?;
+        }
+        Ok(())
+    }
+
+    /// Wait until the PKA operation finishes, or until the timeout expires.
+    pub(crate) fn wait_pka_done() -> Result<(), CryptoError> {
+        Self::wait_until(|| !Self::is_pka_in_use())
+    }
+
     pub fn reset(&mut self) {
         // Resetting is performed using SysCtrl.
         // TODO: change the SysCtrl API.
@@ -77,14 +169,44 @@ impl Crypto<'_> {
 
     /// Check if the AES resource is in use.
     pub fn is_aes_in_use() -> bool {
+        debug_assert!(
+            crate::sys_ctrl::is_aes_clock_enabled(),
+            "AES clock not enabled, call `enable_aes_in_active_mode` first"
+        );
         Self::aes().ctrl_alg_sel().read().bits() != 0
     }
 
     /// Check if the PKA resource is in use.
     pub fn is_pka_in_use() -> bool {
+        debug_assert!(
+            crate::sys_ctrl::is_pka_clock_enabled(),
+            "PKA clock not enabled, call `enable_pka_in_active_mode` first"
+        );
         Self::pka().function().read().run().bit_is_set()
     }
 
+    /// Wait until the PKA operation finishes, without blocking the executor.
+    ///
+    /// The PKA interrupt wakes up the task when the operation is done, instead of spinning on
+    /// [`Crypto::is_pka_in_use`]. The `PKA` handler must call [`on_pka_interrupt`].
+    pub(crate) async fn wait_pka() {
+        core::future::poll_fn(|cx: &mut Context<'_>| {
+            if !Self::is_pka_in_use() {
+                NVIC::mask(interrupt::PKA);
+                PKA_WAKER.clear();
+                return Poll::Ready(());
+            }
+
+            PKA_WAKER.register(cx.waker());
+            // When the operation finished in the meantime, the pending interrupt fires as soon as
+            // it is unmasked.
+            unsafe { NVIC::unmask(interrupt::PKA) };
+
+            Poll::Pending
+        })
+        .await
+    }
+
     /// Check if the result of the AES operation is available.
     fn is_aes_completed() -> bool {
         Self::aes().ctrl_int_stat().read().result_av().bit_is_set()
@@ -96,17 +218,46 @@ impl Crypto<'_> {
     //}
 }
 
+/// Waker of the task waiting for the PKA operation to finish.
+static PKA_WAKER: WakerCell = WakerCell::new();
+
+/// Handle the `PKA` interrupt for the async bignum and ECC operations.
+///
+/// The HAL does not define the interrupt handler. The handler must call this function:
+///
+/// ```ignore
+/// #[interrupt]
+/// fn PKA() {
+///     crypto::on_pka_interrupt();
+/// }
+/// ```
+pub fn on_pka_interrupt() {
+    NVIC::mask(interrupt::PKA);
+    PKA_WAKER.wake();
+}
+
 impl<'p> Crypto<'p> {
     /// Create a new crypto instance.
-    pub fn new(
-        #[allow(unused_variables)] aes: &'p mut Aes,
-        #[allow(unused_variables)] pka: &'p mut Pka,
-    ) -> Self {
+    pub fn new(aes: &'p mut Aes, pka: &'p mut Pka) -> Self {
+        debug_assert!(
+            crate::sys_ctrl::is_aes_clock_enabled() || crate::sys_ctrl::is_pka_clock_enabled(),
+            "AES and PKA clocks not enabled, call `enable_aes_in_active_mode` or `enable_pka_in_active_mode` first"
+        );
+
         Self {
-            _aes: PhantomData,
-            _pka: PhantomData,
+            aes,
+            pka,
+            wakeups: crate::sys_ctrl::retention_lost_count(),
+            iv_byte_order: Default::default(),
         }
     }
+
+    /// Release the AES and PKA peripherals.
+    ///
+    /// The keys loaded in the key store stay loaded, and an ongoing operation is not stopped.
+    pub fn free(self) -> (&'p mut Aes, &'p mut Pka) {
+        (self.aes, self.pka)
+    }
 }
 
 pub struct PkaRam {}
@@ -119,7 +270,7 @@ impl PkaRam {
     /// aligned. We assume that the offset that is also aligned.
     fn write_slice(data: &[u32], offset: usize) -> usize {
         assert!(offset % 8 == 0);
-        assert!(offset + data.len() * 4 < Self::PKA_RAM_SIZE);
+        assert!(offset + data.len() * 4 <= Self::PKA_RAM_SIZE);
 
         for (i, d) in data.iter().enumerate() {
             let addr = Self::PKA_RAM_PTR + offset + i * 4;
@@ -128,12 +279,12 @@ impl PkaRam {
             }
         }
 
-        (((4 * data.len()) + 7)/8)*8
+        (4 * data.len()).div_ceil(8) * 8
     }
 
     /// Write data form PKA RAM into a slice.
     fn read_slice(data: &mut [u32], offset: usize) {
-        assert!(offset + data.len() * 4 < Self::PKA_RAM_SIZE);
+        assert!(offset + data.len() * 4 <= Self::PKA_RAM_SIZE);
 
         for (i, d) in data.iter_mut().enumerate() {
             let addr = Self::PKA_RAM_PTR + offset + i * 4;
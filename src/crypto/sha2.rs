@@ -49,7 +49,7 @@ impl Crypto<'_> {
                 state
                     .buf
                     .copy_from_slice(&data[offset..offset + BLOCK_SIZE]);
-                self.new_hash(&mut state);
+                self.new_hash(&mut state)?;
                 state.new_digest = false;
                 state.length += (BLOCK_SIZE << 3) as u64;
                 offset += BLOCK_SIZE;
@@ -62,7 +62,7 @@ impl Crypto<'_> {
                 len -= n;
 
                 if state.curlen == BLOCK_SIZE as u32 && len > 0 {
-                    self.new_hash(&mut state);
+                    self.new_hash(&mut state)?;
                     state.new_digest = false;
                     state.length += (BLOCK_SIZE << 3) as u64;
                     state.curlen = 0;
@@ -75,7 +75,7 @@ impl Crypto<'_> {
                 state
                     .buf
                     .copy_from_slice(&data[offset..offset + BLOCK_SIZE]);
-                self.resume_hash(&mut state);
+                self.resume_hash(&mut state)?;
                 state.length += (BLOCK_SIZE << 3) as u64;
                 offset += BLOCK_SIZE;
                 len -= BLOCK_SIZE;
@@ -87,26 +87,29 @@ impl Crypto<'_> {
                 len -= n;
 
                 if state.curlen == BLOCK_SIZE as u32 && len > 0 {
-                    self.resume_hash(&mut state);
+                    self.resume_hash(&mut state)?;
                     state.length += (BLOCK_SIZE << 3) as u64;
                     state.curlen = 0;
                 }
             }
         }
 
-        self.finalize(&mut state);
+        self.finalize(&mut state)?;
 
-        digest.copy_from_slice(unsafe { &core::mem::transmute::<[u32; 8], [u8; 32]>(state.state) });
+        // The digest words are stored in the byte order the hash engine outputs them (either
+        // written by the DMA or read back from the HASH_DIGEST registers), so write them out
+        // byte per byte instead of relying on the memory layout of the array.
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(state.state.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
 
         Ok(())
     }
 
-    fn new_hash(&mut self, state: &mut Sha256State) {
+    fn new_hash(&mut self, state: &mut Sha256State) -> Result<(), CryptoError> {
+        self.reinit_after_wake();
+
         let aes = Self::aes();
-        // Workaround for AES registers not retained after PM2
-        aes.ctrl_int_cfg().write(|w| w.level().set_bit());
-        aes.ctrl_int_en()
-            .write(|w| w.dma_in_done().set_bit().result_av().set_bit());
 
         // Configure master control module and enable DMA path to the SHA-256 engine.
         // Enable digest readout.
@@ -169,7 +172,7 @@ impl Crypto<'_> {
         }
 
         // Wait for the completion of the operation.
-        while !Self::is_aes_completed() {}
+        Self::wait_until(Self::is_aes_completed)?;
 
         // Clear the interrupt.
         aes.ctrl_int_clr()
@@ -181,14 +184,14 @@ impl Crypto<'_> {
             // Clear mode
             aes.aes_ctrl().write(|w| w.bits(0));
         }
+
+        Ok(())
     }
 
-    fn resume_hash(&mut self, state: &mut Sha256State) {
+    fn resume_hash(&mut self, state: &mut Sha256State) -> Result<(), CryptoError> {
+        self.reinit_after_wake();
+
         let aes = Self::aes();
-        // Workaround for AES registers not retained after PM2.
-        aes.ctrl_int_cfg().write(|w| w.level().set_bit());
-        aes.ctrl_int_en()
-            .write(|w| w.dma_in_done().set_bit().result_av().set_bit());
 
         // Configure master control module and enable the DMA path to the SHA2-256 engine.
         aes.ctrl_alg_sel().write(|w| w.hash().set_bit());
@@ -257,7 +260,7 @@ impl Crypto<'_> {
         }
 
         // Wait for the completion of the operation.
-        while !Self::is_aes_completed() {}
+        Self::wait_until(Self::is_aes_completed)?;
 
         // Read the digest
         state.state[0] = aes.hash_digest_a().read().bits();
@@ -282,19 +285,22 @@ impl Crypto<'_> {
             // Clear mode
             aes.aes_ctrl().write(|w| w.bits(0));
         }
+
+        Ok(())
     }
 
-    fn finalize(&mut self, state: &mut Sha256State) {
+    fn finalize(&mut self, state: &mut Sha256State) -> Result<(), CryptoError> {
         state.length += (state.curlen << 3) as u64;
         state.final_digest = true;
 
         if state.new_digest {
-            self.new_hash(state);
+            self.new_hash(state)?;
         } else {
-            self.resume_hash(state);
+            self.resume_hash(state)?;
         }
 
         state.new_digest = false;
         state.final_digest = false;
+        Ok(())
     }
 }
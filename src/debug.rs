@@ -0,0 +1,235 @@
+//! Formatters for debugging
+//!
+//! These are only available with the `debug` feature.
+
+use core::fmt;
+
+/// Formats bytes as space separated hexadecimal values.
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// An address in an IEEE 802.15.4 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Address {
+    Short(u16),
+    /// The extended address, as it is sent (little-endian).
+    Extended([u8; 8]),
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Short(addr) => write!(f, "{:#06x}", addr),
+            Address::Extended(addr) => {
+                for (i, b) in addr.iter().rev().enumerate() {
+                    if i != 0 {
+                        f.write_str(":")?;
+                    }
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Formats an IEEE 802.15.4 frame, decoding the frame control field, the addresses and the
+/// offset of the payload.
+///
+/// The frame is given without the length byte and without the checksum, like the payload of a
+/// [`Frame`](crate::radio::Frame). The addressing fields are decoded following the 2006 version
+/// of the standard. The auxiliary security header is not decoded, it is shown as a hex dump and
+/// skipped to find the payload.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameFormatter<'a>(pub &'a [u8]);
+
+/// Why decoding a frame stopped.
+enum DecodeError {
+    /// The frame is shorter than its header.
+    Truncated,
+    /// Writing to the formatter failed.
+    Fmt(fmt::Error),
+}
+
+impl From<fmt::Error> for DecodeError {
+    fn from(e: fmt::Error) -> Self {
+        DecodeError::Fmt(e)
+    }
+}
+
+impl FrameFormatter<'_> {
+    /// Read an address of the given addressing mode at `offset`, advancing the offset.
+    fn read_address(&self, mode: u16, offset: &mut usize) -> Result<Option<Address>, DecodeError> {
+        let len = match mode {
+            0b10 => 2,
+            0b11 => 8,
+            _ => return Ok(None),
+        };
+
+        let bytes = self
+            .0
+            .get(*offset..*offset + len)
+            .ok_or(DecodeError::Truncated)?;
+        *offset += len;
+
+        if len == 2 {
+            Ok(Some(Address::Short(u16::from_le_bytes([
+                bytes[0], bytes[1],
+            ]))))
+        } else {
+            let mut addr = [0u8; 8];
+            addr.copy_from_slice(bytes);
+            Ok(Some(Address::Extended(addr)))
+        }
+    }
+
+    /// Read a PAN ID at `offset`, advancing the offset.
+    fn read_pan_id(&self, offset: &mut usize) -> Result<u16, DecodeError> {
+        let bytes = self
+            .0
+            .get(*offset..*offset + 2)
+            .ok_or(DecodeError::Truncated)?;
+        *offset += 2;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+impl fmt::Display for FrameFormatter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.len() < 2 {
+            return write!(f, "truncated frame: {}", HexDump(self.0));
+        }
+
+        let fcf = u16::from_le_bytes([self.0[0], self.0[1]]);
+        let frame_type = fcf & 0b111;
+        let security = fcf & (1 << 3) != 0;
+        let frame_pending = fcf & (1 << 4) != 0;
+        let ack_request = fcf & (1 << 5) != 0;
+        let pan_id_compression = fcf & (1 << 6) != 0;
+        let seq_suppression = fcf & (1 << 8) != 0;
+        let dest_mode = (fcf >> 10) & 0b11;
+        let version = (fcf >> 12) & 0b11;
+        let src_mode = (fcf >> 14) & 0b11;
+
+        let frame_type_name = match frame_type {
+            0b000 => "beacon",
+            0b001 => "data",
+            0b010 => "ack",
+            0b011 => "command",
+            _ => "reserved",
+        };
+
+        write!(
+            f,
+            "{} (fcf {:#06x}, version {}",
+            frame_type_name, fcf, version
+        )?;
+        if security {
+            f.write_str(", security")?;
+        }
+        if frame_pending {
+            f.write_str(", pending")?;
+        }
+        if ack_request {
+            f.write_str(", ack request")?;
+        }
+        if pan_id_compression {
+            f.write_str(", pan id compression")?;
+        }
+        f.write_str(")")?;
+
+        let mut offset = 2;
+
+        // The sequence number can only be suppressed in frames of the 2015 version.
+        if !(version == 0b10 && seq_suppression) {
+            match self.0.get(offset) {
+                Some(seq) => write!(f, " seq {}", seq)?,
+                None => return f.write_str(" truncated"),
+            }
+            offset += 1;
+        }
+
+        let parsed: Result<(), DecodeError> = (|| {
+            if dest_mode != 0 {
+                let pan_id = self.read_pan_id(&mut offset)?;
+                let addr = self
+                    .read_address(dest_mode, &mut offset)?
+                    .ok_or(DecodeError::Truncated)?;
+                write!(f, " dst {:#06x}/{}", pan_id, addr)?;
+            }
+
+            if src_mode != 0 {
+                let pan_id = if pan_id_compression && dest_mode != 0 {
+                    None
+                } else {
+                    Some(self.read_pan_id(&mut offset)?)
+                };
+                let addr = self
+                    .read_address(src_mode, &mut offset)?
+                    .ok_or(DecodeError::Truncated)?;
+                match pan_id {
+                    Some(pan_id) => write!(f, " src {:#06x}/{}", pan_id, addr)?,
+                    None => write!(f, " src {}", addr)?,
+                }
+            }
+
+            if security {
+                // Security control, frame counter and key identifier. The frame counter can only
+                // be suppressed in frames of the 2015 version.
+                let control = *self.0.get(offset).ok_or(DecodeError::Truncated)?;
+                let counter_len = if version == 0b10 && control & (1 << 5) != 0 {
+                    0
+                } else {
+                    4
+                };
+                let key_id_len = match (control >> 3) & 0b11 {
+                    0b00 => 0,
+                    0b01 => 1,
+                    0b10 => 5,
+                    _ => 9,
+                };
+
+                let len = 1 + counter_len + key_id_len;
+                let header = self
+                    .0
+                    .get(offset..offset + len)
+                    .ok_or(DecodeError::Truncated)?;
+                write!(f, " [aux security header {}]", HexDump(header))?;
+                offset += len;
+            }
+
+            Ok(())
+        })();
+
+        match parsed {
+            Ok(()) => {}
+            Err(DecodeError::Truncated) => return f.write_str(" truncated"),
+            Err(DecodeError::Fmt(e)) => return Err(e),
+        }
+
+        write!(
+            f,
+            " payload @{} ({} bytes): {}",
+            offset,
+            self.0.len() - offset,
+            HexDump(&self.0[offset..])
+        )
+    }
+}
+
+/// Write the decoded frame into a [`fmt::Write`] sink, see [`FrameFormatter`].
+pub fn write_frame(w: &mut impl fmt::Write, frame: &[u8]) -> fmt::Result {
+    write!(w, "{}", FrameFormatter(frame))
+}
@@ -4,8 +4,9 @@ use core::convert::Infallible;
 
 pub use crate::hal::delay::DelayNs;
 use crate::sys_ctrl::ClockConfig;
+use crate::time::MonoTimer;
 use cortex_m::peripheral::syst::SystClkSource;
-use cortex_m::peripheral::SYST;
+use cortex_m::peripheral::{DWT, SYST};
 
 pub struct Delay {
     clocks: ClockConfig,
@@ -38,3 +39,55 @@ impl DelayNs for Delay {
         self.syst.disable_counter();
     }
 }
+
+/// A delay that spins on the cycle counter of the DWT.
+///
+/// Unlike [`Delay`], this does not need the SYST peripheral, which stays available for e.g. a
+/// scheduler.
+#[derive(Clone, Copy)]
+pub struct DwtDelay {
+    freq: u32,
+}
+
+impl DwtDelay {
+    /// Create a new delay, using the cycle counter enabled by the `MonoTimer`.
+    pub fn new(timer: MonoTimer) -> Self {
+        Self {
+            freq: timer.frequency(),
+        }
+    }
+
+    /// Spin for the amount of cycles.
+    ///
+    /// The cycle counter wraps around every 2^32 cycles (about 134 s at 32 MHz), so long delays
+    /// are split into multiple waits.
+    fn delay_cycles(&self, mut cycles: u64) {
+        const MAX_WAIT: u64 = 1 << 31;
+
+        while cycles > 0 {
+            let wait = cycles.min(MAX_WAIT) as u32;
+            let start = DWT::cycle_count();
+            while DWT::cycle_count().wrapping_sub(start) < wait {}
+            cycles -= wait as u64;
+        }
+    }
+
+    /// Convert a duration in `unit`s per second to cycles.
+    fn to_cycles(self, duration: u32, unit: u64) -> u64 {
+        (duration as u64 * self.freq as u64).div_ceil(unit)
+    }
+}
+
+impl DelayNs for DwtDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay_cycles(self.to_cycles(ns, 1_000_000_000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.delay_cycles(self.to_cycles(us, 1_000_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_cycles(self.to_cycles(ms, 1_000));
+    }
+}
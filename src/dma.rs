@@ -2,8 +2,11 @@
 
 use core::marker::PhantomData;
 
-use cc2538_pac::Udma;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cc2538_pac::{Interrupt, Udma};
 use cortex_m::interrupt::free;
+use cortex_m::peripheral::NVIC;
 
 pub struct Disabled;
 pub struct Enabled;
@@ -17,8 +20,10 @@ struct DmaChannelConfig {
     _unused: u32,
 }
 
+/// The control table, the primary control structures of the 32 channels followed by their
+/// alternate control structures.
 #[repr(align(1024))]
-struct DmaChannelConfigArray([DmaChannelConfig; 32]);
+struct DmaChannelConfigArray([DmaChannelConfig; 64]);
 
 #[used]
 #[link_section = ".dma_channel_config"]
@@ -28,9 +33,12 @@ static mut DMA_CHANNEL_CONFIG: DmaChannelConfigArray = DmaChannelConfigArray(
         dest_end_ptr: 0,
         control_word: 0,
         _unused: 0,
-    }; 32],
+    }; 64],
 );
 
+/// The channels that raise the uDMA software interrupt when their transfer is complete.
+static LISTEN_COMPLETE: AtomicU32 = AtomicU32::new(0);
+
 /// Extension trait to split the uDMA controller in its channels
 pub trait DmaExt {
     type Part;
@@ -55,6 +63,18 @@ impl DmaExt for Udma {
 }
 
 impl<STATE> Dma<STATE> {
+    /// Check if a bus error occured during a transfer
+    #[inline]
+    pub fn has_bus_error(&self) -> bool {
+        self.udma.errclr().read().errclr().bit_is_set()
+    }
+
+    /// Clear the bus error
+    #[inline]
+    pub fn clear_bus_error(&self) {
+        self.udma.errclr().write(|w| w.errclr().set_bit());
+    }
+
     #[inline]
     pub fn get_state(&self) -> DmaState {
         let state = unsafe { (*Udma::ptr()).stat().read().state().bits() };
@@ -137,6 +157,107 @@ impl Channel {
         mode.into()
     }
 
+    /// Return the number of this channel
+    #[inline]
+    pub fn number(&self) -> usize {
+        self.channel
+    }
+
+    /// Return the other control structure of this channel
+    ///
+    /// The ping-pong and scatter-gather transfer modes use both the primary and the alternate
+    /// control structure of a channel.
+    #[inline]
+    pub fn other_structure(&self) -> Channel {
+        let index = 32 * (!self.alternate as usize) + self.channel;
+        free(|_| Channel {
+            control_word: ChannelControlWord(unsafe { DMA_CHANNEL_CONFIG.0[index].control_word }),
+            channel: self.channel,
+            alternate: !self.alternate,
+        })
+    }
+
+    /// Return the amount of transfers that are still pending for this channel
+    #[inline]
+    pub fn remaining_transfers(&self) -> usize {
+        let control_word = free(|_| unsafe {
+            DMA_CHANNEL_CONFIG.0[32 * self.alternate as usize + self.channel].control_word
+        });
+
+        if TransferMode::from(control_word & 0x07) == TransferMode::Stop {
+            0
+        } else {
+            ((control_word >> 4) & 0b11_1111_1111) as usize + 1
+        }
+    }
+
+    /// Disable the channel
+    #[inline]
+    pub fn disable(&self) {
+        free(|_| unsafe {
+            (*Udma::ptr()).enaclr().write(|w| w.bits(1 << self.channel));
+        });
+    }
+
+    /// Raise the `UDMA_SW` interrupt when the transfer of this channel is complete
+    ///
+    /// The `UDMA_ERROR` interrupt is unmasked as well, such that a bus error is reported
+    /// instead of the transfer appearing to be stuck.
+    #[inline]
+    pub fn listen_complete(&self) {
+        LISTEN_COMPLETE.fetch_or(1 << self.channel, Ordering::AcqRel);
+        unsafe {
+            NVIC::unmask(Interrupt::UDMA_SW);
+            NVIC::unmask(Interrupt::UDMA_ERROR);
+        }
+    }
+
+    /// Stop raising the `UDMA_SW` interrupt for this channel
+    ///
+    /// The interrupts are masked when no channel is listening anymore.
+    #[inline]
+    pub fn unlisten_complete(&self) {
+        let previous = LISTEN_COMPLETE.fetch_and(!(1 << self.channel), Ordering::AcqRel);
+        if previous & !(1 << self.channel) == 0 {
+            NVIC::mask(Interrupt::UDMA_SW);
+            NVIC::mask(Interrupt::UDMA_ERROR);
+        }
+    }
+
+    /// Check if the transfer of this channel is complete
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        unsafe { (*Udma::ptr()).chis().read().bits() & (1 << self.channel) != 0 }
+    }
+
+    /// Clear the complete status of this channel
+    #[inline]
+    pub fn clear_complete(&self) {
+        unsafe {
+            (*Udma::ptr()).chis().write(|w| w.bits(1 << self.channel));
+        }
+    }
+
+    /// Return the status of the transfer of this channel
+    ///
+    /// The uDMA disables a channel when a bus error occurs during its transfer, so a pending
+    /// bus error with this channel disabled before finishing is reported as an error.
+    #[inline]
+    pub fn transfer_status(&self) -> TransferStatus {
+        let udma = unsafe { &*Udma::ptr() };
+
+        if self.is_complete() {
+            TransferStatus::Complete
+        } else if udma.errclr().read().errclr().bit_is_set()
+            && udma.enaset().read().bits() & (1 << self.channel) == 0
+            && self.get_mode() != TransferMode::Stop
+        {
+            TransferStatus::Error
+        } else {
+            TransferStatus::InProgress
+        }
+    }
+
     /// Set the source end address for this channel
     #[inline]
     pub fn set_source_end_address(&self, address: u32) {
@@ -268,8 +389,10 @@ impl Channel {
     }
 
     /// Set the transfer size (the amount of transfers, not in bytes/bits) for this channel
+    ///
+    /// The field is 10 bits wide, so at most 1024 transfers are done.
     #[inline]
-    pub fn set_transfer_size(&mut self, size: u8) {
+    pub fn set_transfer_size(&mut self, size: u16) {
         self.control_word.set_transfer_size(size);
         self.set_config();
     }
@@ -302,6 +425,30 @@ impl Channel {
         self.set_config();
     }
 
+    /// Configure the channel with a [`ChannelBuilder`]
+    ///
+    /// All the fields set in `f` are written to the channel configuration in one critical
+    /// section, such that an interrupt never observes a half configured channel.
+    #[inline]
+    pub fn configure(&mut self, f: impl FnOnce(&mut ChannelBuilder)) {
+        let index = 32 * (self.alternate as usize) + self.channel;
+
+        free(|_| unsafe {
+            let mut builder = ChannelBuilder {
+                src_end_ptr: DMA_CHANNEL_CONFIG.0[index].src_end_ptr,
+                dest_end_ptr: DMA_CHANNEL_CONFIG.0[index].dest_end_ptr,
+                control_word: self.control_word,
+            };
+
+            f(&mut builder);
+
+            DMA_CHANNEL_CONFIG.0[index].src_end_ptr = builder.src_end_ptr;
+            DMA_CHANNEL_CONFIG.0[index].dest_end_ptr = builder.dest_end_ptr;
+            DMA_CHANNEL_CONFIG.0[index].control_word = builder.control_word.into();
+            self.control_word = builder.control_word;
+        });
+    }
+
     /// Set the config word in the DMA_CHANNEL_CONFIG array
     #[inline]
     fn set_config(&self) {
@@ -312,6 +459,80 @@ impl Channel {
     }
 }
 
+/// Configuration of a channel that is written at once, see [`Channel::configure`]
+pub struct ChannelBuilder {
+    src_end_ptr: u32,
+    dest_end_ptr: u32,
+    control_word: ChannelControlWord,
+}
+
+impl ChannelBuilder {
+    /// Set the source end address
+    #[inline]
+    pub fn set_source_end_address(&mut self, address: u32) -> &mut Self {
+        self.src_end_ptr = address;
+        self
+    }
+
+    /// Set the destination end addresss
+    #[inline]
+    pub fn set_destination_end_address(&mut self, address: u32) -> &mut Self {
+        self.dest_end_ptr = address;
+        self
+    }
+
+    /// Set the destination address increment
+    #[inline]
+    pub fn set_destination_increment(&mut self, increment: AddressIncrement) -> &mut Self {
+        self.control_word.set_destination_increment(increment);
+        self
+    }
+
+    /// Set the destination data size
+    #[inline]
+    pub fn set_destination_size(&mut self, size: DataSize) -> &mut Self {
+        self.control_word.set_destination_size(size);
+        self
+    }
+
+    /// Set the source address increment
+    #[inline]
+    pub fn set_source_increment(&mut self, increment: AddressIncrement) -> &mut Self {
+        self.control_word.set_source_increment(increment);
+        self
+    }
+
+    /// Set the source data size
+    #[inline]
+    pub fn set_source_size(&mut self, size: DataSize) -> &mut Self {
+        self.control_word.set_source_size(size);
+        self
+    }
+
+    /// Set the arbitration size
+    #[inline]
+    pub fn set_arbitration_size(&mut self, size: Arbitration) -> &mut Self {
+        self.control_word.set_arbitration_size(size);
+        self
+    }
+
+    /// Set the transfer size (the amount of transfers, not in bytes/bits)
+    ///
+    /// The field is 10 bits wide, so at most 1024 transfers are done.
+    #[inline]
+    pub fn set_transfer_size(&mut self, size: u16) -> &mut Self {
+        self.control_word.set_transfer_size(size);
+        self
+    }
+
+    /// Set the transfer mode
+    #[inline]
+    pub fn set_transfer_mode(&mut self, mode: TransferMode) -> &mut Self {
+        self.control_word.set_transfer_mode(mode);
+        self
+    }
+}
+
 impl ChannelControlWord {
     #[inline]
     fn set_transfer_mode(&mut self, mode: TransferMode) {
@@ -324,7 +545,7 @@ impl ChannelControlWord {
     }
 
     #[inline]
-    fn set_transfer_size(&mut self, size: u8) {
+    fn set_transfer_size(&mut self, size: u16) {
         self.0 = (self.0 & !(0b11_1111_1111 << 4)) | ((size as u32 & 0b11_1111_1111) << 4);
     }
 
@@ -354,6 +575,15 @@ impl ChannelControlWord {
     }
 }
 
+/// The status of the transfer of a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferStatus {
+    InProgress,
+    Complete,
+    /// The transfer was stopped by a bus error
+    Error,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Priority {
     Default,
@@ -18,6 +18,7 @@ pub trait GpioExt {
 
 /// Enum to select a direction for the pin
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Input = 0,
     Output = 1,
@@ -88,6 +89,24 @@ pub enum OutputFunction {
     Gpt3Cp2 = 0x14,
 }
 
+/// An IOC register that selects the pin used as input of a peripheral signal.
+///
+/// This is implemented for the input select registers of the IOC, like [`UartrxdUart1`], and is
+/// used with `into_alt_input_function` on a pin.
+pub trait InputFunction {
+    /// Select the pin, given as `port * 8 + pin`, as input of the peripheral signal.
+    fn select_pin(&mut self, pin_selector: u32);
+}
+
+/// A GPIO input pin, identified by its pin selector.
+///
+/// This is implemented for the input pins, also after their number or port is erased, and is
+/// used to configure their power-up interrupt with [`crate::ioc::PowerUp`].
+pub trait PinSelector {
+    /// Return the number of the port times 8 plus the number of the pin, e.g. 17 for PC1.
+    fn pin_selector(&self) -> u32;
+}
+
 macro_rules! gpio {
     (
         [
@@ -132,6 +151,26 @@ macro_rules! gpio {
         }
 
         impl<MODE> PXx<MODE> {
+            /// Read back the direction of the pin from the DIR register.
+            pub fn direction(&self) -> Direction {
+                let dir = match &self.gpio {
+                    $(
+                        Gpio::$gpio_enum => unsafe { (*$GPIOX::ptr()).dir().read().dir().bits() },
+                    )*
+                };
+
+                if dir & (1 << self.pin) != 0 {
+                    Direction::Output
+                } else {
+                    Direction::Input
+                }
+            }
+
+            /// Check if the pin is configured as an output in the DIR register.
+            pub fn is_output(&self) -> bool {
+                self.direction() == Direction::Output
+            }
+
             fn set_afsel(&mut self, set: bool) {
                 match &self.gpio {
                     $(
@@ -160,6 +199,16 @@ macro_rules! gpio {
             )+
         }
 
+        $(
+            impl InputFunction for $alt_out_reg {
+                fn select_pin(&mut self, pin_selector: u32) {
+                    paste! {
+                    self.[<$alt_out_reg:snake>]().write(|w| unsafe { w.bits(pin_selector) });
+                    }
+                }
+            }
+        )+
+
         impl<MODE> ErrorType for PXx<Output<MODE>> {
             type Error = core::convert::Infallible;
         }
@@ -214,6 +263,12 @@ macro_rules! gpio {
             }
         }
 
+        impl<MODE> PinSelector for PXx<Input<MODE>> {
+            fn pin_selector(&self) -> u32 {
+                (self.gpio as u32 * 8) + self.pin as u32
+            }
+        }
+
 
         $(
             pub mod $gpiox {
@@ -229,9 +284,11 @@ macro_rules! gpio {
                 use super::{
                     Input, Output, OutputEnable, PullUpEnable, PullDownEnable,
                     AnalogEnable, GpioExt, PXx, Gpio, Direction, PadOveride,
-                    OutputFunction, AltFunc,
+                    OutputFunction, InputFunction, PinSelector, AltFunc,
                 };
 
+                use crate::ioc::{I2cmsscl, I2cmssda};
+
                 /// GPIO parts
                 #[derive(Debug)]
                 pub struct Parts {
@@ -315,10 +372,28 @@ macro_rules! gpio {
 
                 }
 
+                impl<MODE> PinSelector for $PXx<Input<MODE>> {
+                    fn pin_selector(&self) -> u32 {
+                        (Gpio::$gpio_enum as u32 * 8) + self.pin as u32
+                    }
+                }
+
                 /// Opaque DATA register
                 #[derive(Debug)]
                 pub struct DATA;
 
+                impl DATA {
+                    /// Read the level of all the pins of the port in one access, where bit `n`
+                    /// is the level of pin `n`.
+                    ///
+                    /// Only the bits of pins that are configured as inputs are meaningful.
+                    pub fn read_port(&self) -> u8 {
+                        let addr = $GPIOX::ptr() as *mut u32;
+                        // The address bits [9:2] mask the data register, select all the pins.
+                        unsafe { *addr.offset(0xff) as u8 }
+                    }
+                }
+
                 /// Opaque DIR register
                 #[derive(Debug)]
                 pub struct DIR;
@@ -357,6 +432,14 @@ macro_rules! gpio {
                             }
                         }
 
+                        /// Erase the pin number, but keep the port in the type.
+                        pub fn erase_number(self) -> $PXx<MODE> {
+                            $PXx {
+                                pin: $pin,
+                                _mode: self._mode,
+                            }
+                        }
+
                         /// Configure the pin to operate as an output pin
                         pub fn into_output_enable_output(
                             self,
@@ -426,6 +509,21 @@ macro_rules! gpio {
 
                         }
 
+                        /// Read back the direction of the pin from the DIR register.
+                        pub fn direction(&self) -> Direction {
+                            let dir = unsafe { (*$GPIOX::ptr()).dir().read().dir().bits() };
+                            if dir & (1 << $pin) != 0 {
+                                Direction::Output
+                            } else {
+                                Direction::Input
+                            }
+                        }
+
+                        /// Check if the pin is configured as an output in the DIR register.
+                        pub fn is_output(&self) -> bool {
+                            self.direction() == Direction::Output
+                        }
+
                         pub(crate) const fn as_pin_selector(&self) -> u32 {
                             (Gpio::$gpio_enum as u32 * 8) + $pin as u32
                         }
@@ -459,6 +557,76 @@ macro_rules! gpio {
                             $PXi { _mode: PhantomData }
                         }
 
+                        /// Set the pin as an alternative function input pin.
+                        ///
+                        /// Arguments:
+                        ///
+                        /// * `afsel`: The port register to enable hardware alternate function for this pin.
+                        /// * `func`: The IOC register of the peripheral input, for example
+                        ///   [`UartrxdUart1`](crate::ioc::UartrxdUart1) to use this pin as RX of
+                        ///   UART1.
+                        pub fn into_alt_input_function(self, dir: &mut DIR, afsel: &mut AFSEL, pad_over: &mut $padover, func: &mut impl InputFunction) -> $PXi<AltFunc> {
+                            self.set_direction(dir, Direction::Input);
+
+                            Self::set_overide_configuretion_register(
+                                pad_over,
+                                PadOveride::Disabled,
+                            );
+
+                            // Set the specific pin to an alternate function
+                            afsel.afsel().modify(|r, w| unsafe {
+                                w.afsel().bits(
+                                    (r.afsel().bits() & !(1 << $pin)) | (1 << $pin))
+                            });
+
+                            // Route the pin to the input of the peripheral
+                            func.select_pin(self.as_pin_selector());
+
+                            $PXi { _mode: PhantomData }
+                        }
+
+                        /// Set the pin as SDA of the I2C master.
+                        ///
+                        /// SDA is both an output and an input of the I2C peripheral, which drives
+                        /// it as an open-drain line. The output function and the input select
+                        /// register are both set, and the pad override is disabled such that the
+                        /// output driver is not forced on, which would keep the line from being
+                        /// released. The bus needs external pull-up resistors.
+                        pub fn into_i2c_sda(self, dir: &mut DIR, afsel: &mut AFSEL, afsel_reg: &mut $afsel, pad_over: &mut $padover, input: &mut I2cmssda) -> $PXi<AltFunc> {
+                            self.into_i2c_function(dir, afsel, afsel_reg, pad_over, OutputFunction::I2cSda, input)
+                        }
+
+                        /// Set the pin as SCL of the I2C master.
+                        ///
+                        /// SCL is configured like SDA, see [`Self::into_i2c_sda`], since slaves
+                        /// can stretch the clock.
+                        pub fn into_i2c_scl(self, dir: &mut DIR, afsel: &mut AFSEL, afsel_reg: &mut $afsel, pad_over: &mut $padover, input: &mut I2cmsscl) -> $PXi<AltFunc> {
+                            self.into_i2c_function(dir, afsel, afsel_reg, pad_over, OutputFunction::I2cScl, input)
+                        }
+
+                        fn into_i2c_function(self, dir: &mut DIR, afsel: &mut AFSEL, afsel_reg: &mut $afsel, pad_over: &mut $padover, func: OutputFunction, input: &mut impl InputFunction) -> $PXi<AltFunc> {
+                            // The direction is controlled by the peripheral.
+                            self.set_direction(dir, Direction::Input);
+
+                            Self::set_overide_configuretion_register(
+                                pad_over,
+                                PadOveride::Disabled,
+                            );
+
+                            afsel.afsel().modify(|r, w| unsafe {
+                                w.afsel().bits(
+                                    (r.afsel().bits() & !(1 << $pin)) | (1 << $pin))
+                            });
+
+                            paste! {
+                            afsel_reg.[<$afsel:snake>]().write(|w| unsafe { w.bits(func as u32 ) });
+                            }
+
+                            input.select_pin(self.as_pin_selector());
+
+                            $PXi { _mode: PhantomData }
+                        }
+
                         pub fn set_pad_overide(&mut self, pad_over: &mut $padover, overide: PadOveride) {
                             Self::set_overide_configuretion_register(
                                 pad_over,
@@ -502,6 +670,12 @@ macro_rules! gpio {
                             Ok(unsafe { *addr.offset(offset) == 0  })
                         }
                     }
+
+                    impl<MODE> PinSelector for $PXi<Input<MODE>> {
+                        fn pin_selector(&self) -> u32 {
+                            self.as_pin_selector()
+                        }
+                    }
                 )+
             }
         )+
@@ -1,14 +1,33 @@
+use core::cell::Cell;
 use core::marker::PhantomData;
+use core::task::{Context, Poll};
+use core::time::Duration;
 
+use crate::interrupt::{InterruptExt, WakerCell};
 use crate::sys_ctrl::ClockConfig;
 use cc2538_pac::I2cm;
+use cc2538_pac::Interrupt as interrupt;
 use cortex_m::asm::delay;
+use cortex_m::peripheral::NVIC;
 
 #[derive(Debug)]
 pub struct Disabled;
 #[derive(Debug)]
 pub struct Enabled;
 
+/// Errors of the I2C master transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cError {
+    /// The address or a data byte was not acknowledged.
+    Nack,
+    /// Another master won the arbitration of the bus.
+    ArbitrationLost,
+    /// The clock timeout expired, see [`I2cMaster::set_clock_timeout`].
+    Timeout,
+    /// There are no bytes to transfer.
+    EmptyBuffer,
+}
+
 #[derive(Debug)]
 enum Operation {
     Read,
@@ -26,6 +45,33 @@ enum I2cCommand {
     BurstReceiveStart = 0xb,
 }
 
+impl I2cCommand {
+    /// The command that sends byte `i` of a write of `len` bytes.
+    fn write_step(i: usize, len: usize) -> Self {
+        match i {
+            0 if len == 1 => I2cCommand::SingleSendReceive,
+            0 => I2cCommand::BurstSendStart,
+            i if i == len - 1 => I2cCommand::BurstSendReceiveFinish,
+            _ => I2cCommand::BurstSendCont,
+        }
+    }
+
+    /// The command that receives byte `i` of a read of `len` bytes.
+    ///
+    /// Every byte is acknowledged, except the last one, which is followed by a STOP.
+    fn read_step(i: usize, len: usize) -> Self {
+        match i {
+            0 if len == 1 => I2cCommand::SingleSendReceive,
+            0 => I2cCommand::BurstReceiveStart,
+            i if i == len - 1 => I2cCommand::BurstSendReceiveFinish,
+            _ => I2cCommand::BurstReceiveCont,
+        }
+    }
+}
+
+/// The general call address, which addresses all devices on the bus.
+pub const GENERAL_CALL_ADDR: u8 = 0x00;
+
 /// I2C Master extension trait.
 pub trait I2cmExt {
     type Parts;
@@ -42,8 +88,15 @@ impl I2cmExt for I2cm {
     type Parts = I2cMaster<Disabled>;
 
     fn take(self) -> Self::Parts {
+        debug_assert!(
+            crate::sys_ctrl::is_i2c_clock_enabled(),
+            "I2C clock not enabled, call `enable_i2c_in_active_mode` first"
+        );
+
         I2cMaster {
             i2cm: self,
+            timeout: None,
+            timed_out: Cell::new(false),
             _state: PhantomData,
         }
     }
@@ -52,10 +105,56 @@ impl I2cmExt for I2cm {
 #[derive(Debug)]
 pub struct I2cMaster<STATE = Disabled> {
     i2cm: I2cm,
+    /// The maximum amount of polls of the busy flag, `None` when waiting forever.
+    timeout: Option<u32>,
+    /// Set when the last wait for the I2C master timed out.
+    timed_out: Cell<bool>,
     _state: PhantomData<STATE>,
 }
 
+impl<STATE> InterruptExt for I2cMaster<STATE> {
+    const INTERRUPT: interrupt = interrupt::I2C;
+}
+
+/// The amount of system clock cycles between two polls of the busy flag when a clock timeout
+/// is set.
+const TIMEOUT_POLL_CYCLES: u32 = 100;
+
 impl<STATE> I2cMaster<STATE> {
+    /// Release the I2C master peripheral.
+    ///
+    /// The master function is disabled first, such that the pins can be reconfigured.
+    pub fn free(self) -> I2cm {
+        self.i2cm.cr().modify(|_, w| w.mfe().clear_bit());
+        self.i2cm
+    }
+
+    /// Set the maximum time to wait for a transfer step to complete.
+    ///
+    /// The CC2538 I2C master has no hardware clock-low timeout, so a slave that stretches SCL
+    /// forever keeps the master busy. With a timeout, the blocking transfers stop waiting,
+    /// send a STOP condition and return an error, after which [`I2cMaster::timed_out`] returns
+    /// `true`.
+    ///
+    /// The busy flag is polled every 100 system clock cycles, so the timeout is rounded up to a
+    /// multiple of 100 clock cycles and can be slightly longer because of the time it takes to
+    /// read the register. A zero duration disables the timeout.
+    pub fn set_clock_timeout(&mut self, duration: Duration, clock: &ClockConfig) {
+        let cycles = duration.as_nanos() * clock.sys_freq() as u128 / 1_000_000_000;
+        let polls = cycles.div_ceil(TIMEOUT_POLL_CYCLES as u128);
+
+        self.timeout = if polls == 0 {
+            None
+        } else {
+            Some(polls.min(u32::MAX as u128) as u32)
+        };
+    }
+
+    /// Returns `true` when the last transfer was aborted because of the clock timeout.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.get()
+    }
+
     /// Set the slave address.
     /// Also sets the read/write flag.
     fn set_slave_address(&self, addr: u8, op: Operation) {
@@ -81,12 +180,31 @@ impl I2cMaster<Disabled> {
 
         I2cMaster {
             i2cm: self.i2cm,
+            timeout: self.timeout,
+            timed_out: self.timed_out,
             _state: PhantomData,
         }
     }
 }
 
 impl I2cMaster<Enabled> {
+    /// Disable the I2C master module.
+    ///
+    /// Waits for the ongoing transfer to finish (or the clock timeout to expire), after which
+    /// the master function is disabled and the pins are no longer driven by the I2C module.
+    pub fn disable(self) -> I2cMaster<Disabled> {
+        let _ = self.wait_while_busy();
+
+        self.i2cm.cr().modify(|_, w| w.mfe().clear_bit());
+
+        I2cMaster {
+            i2cm: self.i2cm,
+            timeout: self.timeout,
+            timed_out: self.timed_out,
+            _state: PhantomData,
+        }
+    }
+
     /// Set the bit rate of the I2C bus.
     pub fn set_bit_rate(&self, bit_rate: u32, clock_config: ClockConfig) {
         unsafe {
@@ -119,19 +237,19 @@ impl I2cMaster<Enabled> {
     }
 
     /// Blocking single byte write.
-    pub fn single_write(&self, addr: u8, data: u8) -> Result<(), ()> {
-        self.set_slave_address(addr, Operation::Read);
+    pub fn single_write(&self, addr: u8, data: u8) -> Result<(), I2cError> {
+        self.set_slave_address(addr, Operation::Write);
         self.put_data(data);
 
         self.write_command(I2cCommand::SingleSendReceive);
 
-        while self.is_busy() {}
+        self.wait_while_busy()?;
 
         Ok(())
     }
 
     /// Blocking multiple bytes write.
-    pub fn burst_write(&self, addr: u8, data: &[u8]) -> Result<(), ()> {
+    pub fn burst_write(&self, addr: u8, data: &[u8]) -> Result<(), I2cError> {
         if data.len() == 1 {
             return self.single_write(addr, data[0]);
         }
@@ -140,59 +258,260 @@ impl I2cMaster<Enabled> {
 
         for (i, b) in data.iter().enumerate() {
             self.put_data(*b);
+            self.write_command(I2cCommand::write_step(i, data.len()));
 
-            if i == 0 {
-                self.write_command(I2cCommand::BurstSendStart);
-            } else if i == data.len() - 1 {
-                self.write_command(I2cCommand::BurstSendReceiveFinish);
-            } else {
-                self.write_command(I2cCommand::BurstSendCont);
-            }
+            self.wait_while_busy()?;
+        }
+
+        Ok(())
+    }
+
+    /// Broadcast `data` to all devices with the general call address.
+    ///
+    /// The first byte of `data` is the second byte of the general call, for example `0x06` to
+    /// reset and write the programmable part of the address of the devices, or `0x04` to only
+    /// write it. The general call is only acknowledged by devices that support it, and an error
+    /// is returned when no device acknowledges the address. Since every device receives the
+    /// same data, a general call can not be directed to one device and can not be read back.
+    pub fn general_call(&self, data: &[u8]) -> Result<(), I2cError> {
+        if data.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
+
+        self.set_slave_address(GENERAL_CALL_ADDR, Operation::Write);
+
+        for (i, b) in data.iter().enumerate() {
+            self.put_data(*b);
+            self.write_command(I2cCommand::write_step(i, data.len()));
 
-            while self.is_busy() {}
+            self.wait_while_busy()?;
+            self.check_error()?;
         }
 
         Ok(())
     }
 
+    /// Send a START byte, followed by a repeated START for the next transfer.
+    ///
+    /// The START byte (`0000 0001`) gives slow devices that poll the bus in software, like some
+    /// older displays and microcontrollers without an I2C peripheral, the time to detect the
+    /// start of a transfer. No device acknowledges it, so the missing acknowledge is not an
+    /// error. The bus is not released: call this right before the transfer to the device,
+    /// which then starts with a repeated START.
+    pub fn send_start_byte(&self) -> Result<(), I2cError> {
+        // Address 0 with the read bit set is the START byte.
+        self.set_slave_address(GENERAL_CALL_ADDR, Operation::Read);
+        self.write_command(I2cCommand::BurstSendStart);
+
+        self.wait_while_busy()
+    }
+
+    /// Return an error when the last operation failed.
+    ///
+    /// The transfer is stopped when the error was caused by a missing acknowledge. After a lost
+    /// arbitration, the bus is already released by the master.
+    fn check_error(&self) -> Result<(), I2cError> {
+        let stat = self.i2cm.stat().read();
+
+        if stat.error().bit_is_clear() {
+            return Ok(());
+        }
+
+        if stat.arblst().bit_is_set() {
+            return Err(I2cError::ArbitrationLost);
+        }
+
+        self.write_command(I2cCommand::BurstSendReceiveErrorStop);
+        Err(I2cError::Nack)
+    }
+
     /// Blocking single byte read.
-    pub fn single_read(&self, addr: u8) -> Result<u8, ()> {
+    pub fn single_read(&self, addr: u8) -> Result<u8, I2cError> {
         self.set_slave_address(addr, Operation::Read);
 
         self.write_command(I2cCommand::SingleSendReceive);
 
-        while self.is_busy() {}
+        self.wait_while_busy()?;
 
         Ok(self.get_data())
     }
 
-    /// Blocking multiple bytes read.
-    pub fn burst_read(&self, addr: u8, buffer: &mut [u8]) -> Result<(), ()> {
+    /// Check if a device acknowledges `addr`.
+    ///
+    /// The CC2538 can not send an address without a data byte, so a single byte is read from
+    /// the device, which does not change its registers. The read byte is discarded. The transfer
+    /// ends with a STOP, also when the address is not acknowledged. Returns `false` when the
+    /// arbitration is lost or the clock timeout expires.
+    pub fn device_present(&self, addr: u8) -> bool {
         self.set_slave_address(addr, Operation::Read);
-        self.write_command(I2cCommand::BurstReceiveStart);
+        self.write_command(I2cCommand::SingleSendReceive);
 
-        while self.is_busy() {}
+        if self.wait_while_busy().is_err() {
+            return false;
+        }
+
+        let stat = self.i2cm.stat().read();
+        let present = stat.adrack().bit_is_clear() && stat.arblst().bit_is_clear();
+
+        let _ = self.get_data();
+
+        present
+    }
+
+    /// Blocking multiple bytes read.
+    ///
+    /// The command that receives a byte also decides how it is acknowledged, so the finish
+    /// command is written before the last byte is received: the master then NACKs that byte
+    /// and sends a STOP, and the slave does not put another byte on the bus. Returns an error
+    /// when `buffer` is empty, when the address is not acknowledged or when the arbitration is
+    /// lost.
+    pub fn burst_read(&self, addr: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        if buffer.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
+
+        self.set_slave_address(addr, Operation::Read);
 
         let len = buffer.len();
         for (i, b) in buffer.iter_mut().enumerate() {
+            self.write_command(I2cCommand::read_step(i, len));
+
+            self.wait_while_busy()?;
+            self.check_error()?;
+
             *b = self.get_data();
+        }
 
-            // TODO(thvdveld): fix the last NACK
-            if i == len - 1 {
-                self.write_command(I2cCommand::BurstSendReceiveFinish);
-                break;
-            } else {
-                self.write_command(I2cCommand::BurstReceiveCont);
-            }
+        Ok(())
+    }
+
+    /// Write multiple bytes, awaiting the I2C master interrupt instead of spinning on the busy
+    /// flag.
+    ///
+    /// Returns an error when `data` is empty, when a byte or the address is not acknowledged
+    /// or when the arbitration is lost. The clock timeout is not used, since the executor is
+    /// not blocked while waiting.
+    pub async fn write_async(&mut self, addr: u8, data: &[u8]) -> Result<(), I2cError> {
+        if data.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
+
+        self.set_slave_address(addr, Operation::Write);
+
+        for (i, b) in data.iter().enumerate() {
+            self.put_data(*b);
+            self.run_command(I2cCommand::write_step(i, data.len()))
+                .await;
+            self.check_error()?;
+        }
+
+        Ok(())
+    }
 
-            while self.is_busy() {}
+    /// Read multiple bytes, awaiting the I2C master interrupt instead of spinning on the busy
+    /// flag.
+    ///
+    /// Returns an error when `buffer` is empty, when the address is not acknowledged or when
+    /// the arbitration is lost. The clock timeout is not used, since the executor is not
+    /// blocked while waiting.
+    pub async fn read_async(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        if buffer.is_empty() {
+            return Err(I2cError::EmptyBuffer);
+        }
+
+        self.set_slave_address(addr, Operation::Read);
+
+        let len = buffer.len();
+        for (i, b) in buffer.iter_mut().enumerate() {
+            self.run_command(I2cCommand::read_step(i, len)).await;
+            self.check_error()?;
+
+            *b = self.get_data();
         }
 
         Ok(())
     }
 
+    /// Write a command and wait for the master interrupt that signals that it completed.
+    async fn run_command(&self, command: I2cCommand) {
+        self.i2cm.icr().write(|w| w.ic().set_bit());
+        self.i2cm.imr().write(|w| w.im().set_bit());
+
+        self.write_command(command);
+
+        core::future::poll_fn(|cx: &mut Context<'_>| {
+            if self.i2cm.ris().read().ris().bit_is_set() {
+                NVIC::mask(interrupt::I2C);
+                I2C_WAKER.clear();
+
+                self.i2cm.icr().write(|w| w.ic().set_bit());
+                self.i2cm.imr().write(|w| w.im().clear_bit());
+                return Poll::Ready(());
+            }
+
+            I2C_WAKER.register(cx.waker());
+            // When the command completed in the meantime, the pending interrupt fires as soon
+            // as it is unmasked.
+            unsafe { NVIC::unmask(interrupt::I2C) };
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Wait until the I2C master is no longer busy.
+    ///
+    /// When the clock timeout expires, the transfer is stopped and an error is returned.
+    fn wait_while_busy(&self) -> Result<(), I2cError> {
+        let expired = match self.timeout {
+            None => {
+                while self.is_busy() {}
+                false
+            }
+            Some(polls) => {
+                let mut remaining = polls;
+                while self.is_busy() && remaining > 0 {
+                    delay(TIMEOUT_POLL_CYCLES);
+                    remaining -= 1;
+                }
+                self.is_busy()
+            }
+        };
+
+        self.timed_out.set(expired);
+
+        if expired {
+            self.write_command(I2cCommand::BurstSendReceiveErrorStop);
+            Err(I2cError::Timeout)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Check if the I2C bus is busy.
     pub fn is_busy(&self) -> bool {
         self.i2cm.stat().read().busy().bit_is_set()
     }
 }
+
+/// Waker of the task waiting for the I2C master to complete a command.
+static I2C_WAKER: WakerCell = WakerCell::new();
+
+/// Handle the `I2C` interrupt for the async transfers of the I2C master.
+///
+/// The I2C master and slave share this interrupt, so the HAL does not define the interrupt
+/// handler. The handler must call this function:
+///
+/// ```ignore
+/// #[interrupt]
+/// fn I2C() {
+///     i2c::on_i2c_interrupt();
+/// }
+/// ```
+///
+/// The interrupt is only masked here, the waiting task clears it after reading the raw
+/// interrupt status.
+pub fn on_i2c_interrupt() {
+    NVIC::mask(interrupt::I2C);
+    I2C_WAKER.wake();
+}
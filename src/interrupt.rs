@@ -0,0 +1,94 @@
+//! NVIC interrupt lines of the peripheral drivers
+//!
+//! The `listen` methods of the drivers enable an event in the interrupt mask register of the
+//! peripheral and unmask the NVIC line of the peripheral, and `unlisten` masks the line again
+//! once no event of the peripheral is enabled anymore. The timers are the exception: their
+//! interrupt handlers belong to the HAL, so their lines are only unmasked by the async
+//! operations. [`InterruptExt`] gives access to the line itself, without having to know its
+//! name.
+
+use core::cell::Cell;
+use core::task::Waker;
+
+use cortex_m::interrupt::{free, Mutex};
+use cortex_m::peripheral::NVIC;
+
+use crate::pac::Interrupt;
+
+/// A driver of a peripheral with an NVIC interrupt line.
+pub trait InterruptExt {
+    /// The NVIC interrupt line of the peripheral.
+    const INTERRUPT: Interrupt;
+
+    /// Unmask the interrupt line in the NVIC.
+    fn unmask_interrupt(&mut self) {
+        unsafe { NVIC::unmask(Self::INTERRUPT) };
+    }
+
+    /// Mask the interrupt line in the NVIC.
+    fn mask_interrupt(&mut self) {
+        NVIC::mask(Self::INTERRUPT);
+    }
+
+    /// Check if the interrupt line is unmasked in the NVIC.
+    fn is_interrupt_unmasked(&self) -> bool {
+        NVIC::is_enabled(Self::INTERRUPT)
+    }
+
+    /// Set the interrupt line pending, such that the interrupt handler runs when it is
+    /// unmasked.
+    fn pend_interrupt(&mut self) {
+        NVIC::pend(Self::INTERRUPT);
+    }
+}
+
+/// The waker of a task that waits for an interrupt, shared with the interrupt handler.
+pub(crate) struct WakerCell(Mutex<Cell<Option<Waker>>>);
+
+impl WakerCell {
+    pub(crate) const fn new() -> Self {
+        Self(Mutex::new(Cell::new(None)))
+    }
+
+    /// Register the waker of the task, replacing the previous one.
+    pub(crate) fn register(&self, waker: &Waker) {
+        free(|cs| self.0.borrow(cs).set(Some(waker.clone())));
+    }
+
+    /// Remove the waker, without waking the task.
+    pub(crate) fn clear(&self) {
+        free(|cs| self.0.borrow(cs).set(None));
+    }
+
+    /// Check if a task registered its waker.
+    pub(crate) fn is_registered(&self) -> bool {
+        free(|cs| {
+            let cell = self.0.borrow(cs);
+            let waker = cell.take();
+            let registered = waker.is_some();
+            cell.set(waker);
+            registered
+        })
+    }
+
+    /// Wake the task and remove its waker.
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = free(|cs| self.0.borrow(cs).take()) {
+            waker.wake();
+        }
+    }
+
+    /// Wake the task and keep its waker, returns `false` when no task registered its waker.
+    pub(crate) fn wake_by_ref(&self) -> bool {
+        free(|cs| {
+            let cell = self.0.borrow(cs);
+            let waker = cell.take();
+            if let Some(waker) = &waker {
+                waker.wake_by_ref();
+            }
+            let registered = waker.is_some();
+            cell.set(waker);
+            registered
+        })
+    }
+}
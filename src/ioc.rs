@@ -5,12 +5,141 @@ use core::marker::PhantomData;
 
 use paste::paste;
 
+use crate::gpio::{InputFunction, PinSelector};
+
 pub trait IocExt {
     type Parts;
 
     fn split(self) -> Self::Parts;
 }
 
+/// Edge of a pin input that generates a power-up interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeEdge {
+    Rising,
+    Falling,
+}
+
+/// Run `$body` with `$r` bound to `$reg` of the GPIO port of `$pin_selector`.
+///
+/// The power-up registers hold one bit for every pin of the four ports, with the bit number
+/// being the pin selector. Like the TI driver library, the registers are accessed through the
+/// port of the pin.
+macro_rules! with_power_up_reg {
+    ($pin_selector:expr, $reg:ident, |$r:ident| $body:expr) => {
+        match $pin_selector / 8 {
+            0 => {
+                let $r = unsafe { &*crate::pac::GpioA::ptr() }.$reg();
+                $body
+            }
+            1 => {
+                let $r = unsafe { &*crate::pac::GpioB::ptr() }.$reg();
+                $body
+            }
+            2 => {
+                let $r = unsafe { &*crate::pac::GpioC::ptr() }.$reg();
+                $body
+            }
+            3 => {
+                let $r = unsafe { &*crate::pac::GpioD::ptr() }.$reg();
+                $body
+            }
+            _ => unreachable!(),
+        }
+    };
+}
+
+/// Power-up (wake-up) configuration of the pins.
+///
+/// A pin with its power-up interrupt enabled wakes the system from PM1, PM2 and PM3 on the
+/// configured edge, which is detected even while the clocks of the GPIO ports are gated. The
+/// power-up interrupt is signalled on the interrupt line of the GPIO port of the pin
+/// (`GPIOA` to `GPIOD`), so the following is needed to wake on a pin edge:
+///
+/// - the pin is a GPIO input, with a pull-up or pull-down through its pad override when it is
+///   not driven externally (e.g. a reed switch to ground),
+/// - the edge is set with [`PowerUp::set_wake_edge`] and the power-up interrupt is enabled
+///   with [`PowerUp::enable_wake`],
+/// - the interrupt line of the port is unmasked in the NVIC, otherwise the edge does not wake
+///   the system,
+/// - the interrupt handler clears the power-up interrupt with [`PowerUp::clear_wake`], as the
+///   line stays asserted until then.
+///
+/// The power-up interrupt is independent of the regular GPIO interrupt, which is configured in
+/// the `IS`, `IBE`, `IEV` and `IE` registers of the port and only works while the port is
+/// clocked. When both are enabled for a pin, an edge in active mode sets both, and both have to
+/// be cleared in the handler (the `IC` register of the port and [`PowerUp::clear_wake`]).
+///
+/// Pins are given as GPIO inputs, see [`PinSelector`].
+pub struct PowerUp {
+    _private: (),
+}
+
+impl PowerUp {
+    /// Set the edge of the pin that generates a power-up interrupt.
+    pub fn set_wake_edge(&mut self, pin: &impl PinSelector, edge: WakeEdge) {
+        let pin_selector = pin.pin_selector();
+        let mask = 1 << pin_selector;
+
+        with_power_up_reg!(pin_selector, p_edge_ctrl, |reg| reg.modify(|r, w| unsafe {
+            match edge {
+                WakeEdge::Rising => w.bits(r.bits() & !mask),
+                WakeEdge::Falling => w.bits(r.bits() | mask),
+            }
+        }));
+    }
+
+    /// Return the edge of the pin that generates a power-up interrupt.
+    pub fn wake_edge(&self, pin: &impl PinSelector) -> WakeEdge {
+        let pin_selector = pin.pin_selector();
+        let bits = with_power_up_reg!(pin_selector, p_edge_ctrl, |reg| reg.read().bits());
+
+        if bits & (1 << pin_selector) != 0 {
+            WakeEdge::Falling
+        } else {
+            WakeEdge::Rising
+        }
+    }
+
+    /// Enable the power-up interrupt of the pin.
+    ///
+    /// Set the edge with [`PowerUp::set_wake_edge`] first, as changing the edge while the
+    /// interrupt is enabled can trigger it.
+    pub fn enable_wake(&mut self, pin: &impl PinSelector) {
+        let pin_selector = pin.pin_selector();
+        with_power_up_reg!(pin_selector, pi_ien, |reg| reg
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << pin_selector)) }));
+    }
+
+    /// Disable the power-up interrupt of the pin.
+    pub fn disable_wake(&mut self, pin: &impl PinSelector) {
+        let pin_selector = pin.pin_selector();
+        with_power_up_reg!(pin_selector, pi_ien, |reg| reg
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << pin_selector)) }));
+    }
+
+    /// Check if the power-up interrupt of the pin is enabled.
+    pub fn is_wake_enabled(&self, pin: &impl PinSelector) -> bool {
+        let pin_selector = pin.pin_selector();
+        with_power_up_reg!(pin_selector, pi_ien, |reg| reg.read().bits()) & (1 << pin_selector) != 0
+    }
+
+    /// Check if the pin generated a power-up interrupt.
+    pub fn is_wake_pending(&self, pin: &impl PinSelector) -> bool {
+        let pin_selector = pin.pin_selector();
+        with_power_up_reg!(pin_selector, irq_detect_ack, |reg| reg.read().bits())
+            & (1 << pin_selector)
+            != 0
+    }
+
+    /// Clear the power-up interrupt of the pin.
+    pub fn clear_wake(&mut self, pin: &impl PinSelector) {
+        let pin_selector = pin.pin_selector();
+        with_power_up_reg!(pin_selector, irq_detect_ack, |reg| reg
+            .write(|w| unsafe { w.bits(1 << pin_selector) }));
+    }
+}
+
 macro_rules! ioc {
     (
         IOC: $IOC:ident,
@@ -32,6 +161,7 @@ macro_rules! ioc {
                 $(
                 pub $pad_out_reg: [<$pad_out_reg:camel>],
                 )+
+                pub power_up: PowerUp,
             }
 
             $(
@@ -59,6 +189,35 @@ macro_rules! ioc {
             }
             )+
 
+            /// Peripheral input signals that can be routed to any pin.
+            ///
+            /// Every variant names the [`InputFunction`] register of the signal, e.g.
+            /// [`PeripheralInput::UartrxdUart0`] for [`UartrxdUart0`].
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum PeripheralInput {
+                $(
+                [<$pad_out_reg:camel>],
+                )+
+            }
+
+            impl Parts {
+                /// Route the input signal of a peripheral to a pin.
+                ///
+                /// This selects the pin with the [`InputFunction`] register of `input`, like
+                /// `into_alt_input_function` on a pin does. `pin_selector` is the number of the
+                /// port times 8 plus the number of the pin, e.g. 17 for PC1. The pin itself
+                /// still has to be configured as an input with its alternate function enabled.
+                pub fn route_input(&mut self, pin_selector: u32, input: PeripheralInput) {
+                    match input {
+                        $(
+                        PeripheralInput::[<$pad_out_reg:camel>] => {
+                            self.$pad_out_reg.select_pin(pin_selector);
+                        }
+                        )+
+                    }
+                }
+            }
+
             impl IocExt for $IOC {
                 type Parts = Parts;
 
@@ -71,6 +230,7 @@ macro_rules! ioc {
                         $(
                         $pad_out_reg: [<$pad_out_reg:camel>],
                         )+
+                        power_up: PowerUp { _private: () },
                     }
                 }
             }
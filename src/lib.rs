@@ -1,4 +1,17 @@
 //! This crate defines the HAL for the CC2538.
+//!
+//! The PAC peripherals are turned into drivers with an extension trait, which uses one of three
+//! verbs:
+//!
+//! - `split` divides a peripheral into independent parts, like the pins of a GPIO port or the
+//!   two halves of a general purpose timer.
+//! - `take` wraps a peripheral that is used as a whole, like [`spi`] and [`i2c`], and is
+//!   followed by `enable` once the driver is configured.
+//! - `constrain` wraps a peripheral that configures the rest of the chip, like [`sys_ctrl`] and
+//!   [`dma`], and is followed by `freeze` or `enable`.
+//!
+//! Drivers that own a peripheral give it back with `free`, which disables it first when its
+//! pins are driven by it.
 
 #![no_std]
 #![feature(adt_const_params)]
@@ -14,10 +27,13 @@ use embedded_hal as hal;
 
 pub mod adc;
 pub mod crypto;
+#[cfg(feature = "debug")]
+pub mod debug;
 pub mod delay;
 pub mod dma;
 pub mod gpio;
 pub mod i2c;
+pub mod interrupt;
 pub mod ioc;
 pub mod radio;
 pub mod rng;
@@ -28,10 +44,19 @@ pub mod sys_ctrl;
 pub mod time;
 pub mod timers;
 
+/// Start address of the information page, written in the factory.
+pub const INFO_PAGE_ADDR: u32 = 0x0028_0000;
+
+/// Location of the IEEE address in the information page.
+///
+/// The 8 bytes are stored in two 32-bit words. When the TI OUI (`00:12:4b`) is found in the
+/// high bytes of the first word, the words are stored swapped.
+pub const IEEE_ADDR_LOCATION: u32 = INFO_PAGE_ADDR + 0x28;
+
 /// Get the IEEE address from fixed memory.
 pub fn get_ieee_address(addr: &mut [u8]) {
     const TI_ADDR: [u8; 3] = [0x00, 0x12, 0x4b];
-    const ADDR_LOCATION: u32 = 0x00280028;
+    const ADDR_LOCATION: u32 = IEEE_ADDR_LOCATION;
 
     if unsafe { core::ptr::read((ADDR_LOCATION + 3) as *const u32) as u8 } == TI_ADDR[0]
         && unsafe { core::ptr::read((ADDR_LOCATION + 2) as *const u32) as u8 } == TI_ADDR[1]
@@ -51,6 +76,59 @@ pub fn get_ieee_address(addr: &mut [u8]) {
     }
 }
 
+/// Information about the chip, written in the information page in the factory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipInfo {
+    /// The chip ID (DIECFG0, bits 31:16).
+    pub chip_id: u16,
+    /// The size of the flash in KB, `None` for a reserved value (DIECFG0, bits 6:4).
+    pub flash_size_kb: Option<u32>,
+    /// The size of the SRAM in KB, `None` for a reserved value (DIECFG0, bits 9:7).
+    pub sram_size_kb: Option<u32>,
+    /// The major revision of the die, 0 for PG1.0 and 2 for PG2.0 (DIECFG2, bits 15:12).
+    pub die_major_revision: u8,
+    /// The minor revision of the die (DIECFG2, bits 11:8).
+    pub die_minor_revision: u8,
+    /// The IEEE address, see [`get_ieee_address`].
+    pub ieee_address: [u8; 8],
+}
+
+/// Read the information about the chip.
+///
+/// The configuration part of the information page is copied to the DIECFG registers of the
+/// flash controller after reset, so the sizes and revisions are read from there.
+pub fn chip_info() -> ChipInfo {
+    let flash_ctrl = unsafe { &*pac::FlashCtrl::ptr() };
+    let diecfg0 = flash_ctrl.diecfg0().read();
+    let diecfg2 = flash_ctrl.diecfg2().read();
+
+    // Both sizes are encoded with one bit per supported size.
+    let flash_size_kb = match diecfg0.flash_size().bits() {
+        0b001 => Some(128),
+        0b010 => Some(256),
+        0b100 => Some(512),
+        _ => None,
+    };
+    let sram_size_kb = match diecfg0.sram_size().bits() {
+        0b001 => Some(8),
+        0b010 => Some(16),
+        0b100 => Some(32),
+        _ => None,
+    };
+
+    let mut ieee_address = [0; 8];
+    get_ieee_address(&mut ieee_address);
+
+    ChipInfo {
+        chip_id: diecfg0.chipid().bits(),
+        flash_size_kb,
+        sram_size_kb,
+        die_major_revision: diecfg2.die_major_revision().bits(),
+        die_minor_revision: diecfg2.die_minor_revision().bits(),
+        ieee_address,
+    }
+}
+
 struct FlashCca {
     _bootloader_backdoor_disable: u32,
     _is_valid: u32,
@@ -3,16 +3,19 @@
 use core::{
     marker::PhantomData,
     ops::{Deref, DerefMut},
+    time::Duration,
 };
 
 use cc2538_pac as pac;
-use cortex_m::peripheral::NVIC;
+use cortex_m::peripheral::{DWT, NVIC};
 use pac::{
     ana_regs, rfcore_ffsm, rfcore_sfr, rfcore_xreg, AnaRegs, Interrupt, RfcoreFfsm, RfcoreSfr,
     RfcoreXreg,
 };
 
 use crate::dma::{self, Dma, Enabled, TransferMode};
+use crate::hal::delay::DelayNs;
+use crate::interrupt::InterruptExt;
 
 use crate::time::*;
 
@@ -91,6 +94,13 @@ pub enum RadioError {
     Collision,
     FailedTransmission,
     IncorrectFrame,
+    InvalidConfig,
+    TooLate,
+    /// The clock of the RF core is not enabled, see
+    /// [`crate::sys_ctrl::SysCtrl::enable_radio_in_active_mode`]
+    NotClocked,
+    /// The MAC timer is not running, see [`RadioDriver::start_mac_timer`]
+    MacTimerStopped,
 }
 
 pub enum Radio<'p> {
@@ -99,10 +109,111 @@ pub enum Radio<'p> {
     Undefined,
 }
 
+/// The source address of a received frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceAddress {
+    Short(u16),
+    /// The extended address, in the (little-endian) order in which it is received
+    Extended([u8; 8]),
+}
+
+/// The destination address of a frame that is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DestinationAddress {
+    /// A short address, 0xFFFF is the broadcast address
+    Short(u16),
+    /// An extended address, in the same (big-endian) order as [`RadioConfig::ext_addr`]
+    Extended([u8; 8]),
+}
+
+/// The short address that indicates that the device has no short address
+const NO_SHORT_ADDR: u16 = 0xFFFE;
+/// The broadcast PAN ID and short address
+const BROADCAST_ADDR: u16 = 0xFFFF;
+
+/// Start address of the RX FIFO RAM, every byte of the FIFO uses a 32-bit word
+const RX_FIFO_RAM_ADDR: u32 = 0x4008_8000;
+/// Amount of bytes in the RX FIFO RAM
+const RX_FIFO_LEN: usize = 128;
+/// Amount of bytes in the TX FIFO
+const TX_FIFO_LEN: usize = 128;
+
 const CHECKSUM_LEN: usize = 2;
 const MAX_PACKET_LEN: usize = 127;
 const MAX_PAYLOAD_LEN: usize = MAX_PACKET_LEN - CHECKSUM_LEN;
-const CCA_THRES: usize = 0xF8;
+/// Cycles between two reads of the random bits, 1 µs at 32 MHz.
+const RANDOM_BITS_DELAY_CYCLES: u32 = 32;
+/// Polls of the RSSI valid flag before the clear channel assessment of a transmission gives up
+const CCA_RSSI_VALID_RETRIES: u32 = 100_000;
+/// The unit backoff period of CSMA-CA (20 symbols, 320 µs) in MAC timer ticks
+const UNIT_BACKOFF_TICKS: u32 = 320 * 32;
+/// The period of the 16-bit MAC timer in ticks, the range of its compare registers
+const MAC_TIMER_PERIOD_TICKS: u32 = 1 << 16;
+/// The longest wait for the TX done event of a transmission in µs, a frame of maximal length
+/// takes about 4.3 ms on air
+const TX_DONE_TIMEOUT_US: u64 = 10_000;
+
+/// The minimum amount of MAC timer ticks (100 µs) between arming a scheduled transmission and
+/// its start, see [`RadioDriver::transmit_at`]
+///
+/// This covers starting the CSP program after the lead time is checked. Preparing the frame
+/// comes on top of this
+pub const TX_AT_MIN_LEAD_TICKS: u32 = 100 * 32;
+
+/// A received frame, without the checksum
+///
+/// The frame owns its payload, such that it can be passed around as a single value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<const N: usize = 127> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Frame<N> {
+    /// Create a new frame from a payload.
+    ///
+    /// Returns `None` when the payload does not fit in the frame or is bigger than the maximum
+    /// payload length of the radio.
+    pub fn new(payload: &[u8]) -> Option<Self> {
+        if payload.len() > N || payload.len() > MAX_PAYLOAD_LEN {
+            return None;
+        }
+
+        let mut buffer = [0u8; N];
+        buffer[..payload.len()].copy_from_slice(payload);
+
+        Some(Self {
+            buffer,
+            len: payload.len(),
+        })
+    }
+
+    /// Return the length of the payload.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` when the payload is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return a slice to the payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Return a mutable slice to the payload.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer[..self.len]
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Frame<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.payload()
+    }
+}
 
 /// Radio configuration
 #[derive(Debug, Copy, Clone)]
@@ -114,6 +225,59 @@ pub struct RadioConfig {
     pub ext_addr: [u8; 8],
 }
 
+/// The interrupts that are enabled when enabling the radio
+#[derive(Debug, Clone, Copy)]
+pub struct EnableConfig<'a> {
+    /// The events to listen to
+    pub events: &'a [Event],
+    /// The error events to listen to
+    pub error_events: &'a [ErrorEvent],
+    /// Unmask the `RF_TXRX` interrupt in the NVIC
+    pub unmask_interrupt: bool,
+    /// The clear channel assessment configuration
+    pub cca: CcaConfig,
+}
+
+impl Default for EnableConfig<'_> {
+    fn default() -> Self {
+        Self {
+            events: &[Event::Fifop, Event::TxDone],
+            error_events: &[ErrorEvent::All],
+            unmask_interrupt: true,
+            cca: CcaConfig::default(),
+        }
+    }
+}
+
+impl EnableConfig<'_> {
+    /// Don't listen to any event, for using the radio in poll mode
+    pub const fn polling() -> Self {
+        Self {
+            events: &[],
+            error_events: &[],
+            unmask_interrupt: false,
+            cca: CcaConfig::standard(),
+        }
+    }
+}
+
+impl RadioConfig {
+    /// Check that the configuration can be applied to the radio
+    ///
+    /// The PAN IDs must fit in 16 bits and the short address can not be the broadcast address
+    pub fn validate(&self) -> Result<(), RadioError> {
+        if self.src_pan_id > 0xFFFF || self.dst_pan_id > 0xFFFF {
+            return Err(RadioError::InvalidConfig);
+        }
+
+        if self.short_addr == 0xFFFF {
+            return Err(RadioError::InvalidConfig);
+        }
+
+        Ok(())
+    }
+}
+
 impl Default for RadioConfig {
     fn default() -> Self {
         Self {
@@ -134,6 +298,88 @@ pub enum RxMode {
     SymbolSearchDisabled = 0x11,
 }
 
+/// The condition under which the clear channel assessment reports a clear channel
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CcaMode {
+    /// The channel is always clear
+    AlwaysClear = 0b00,
+    /// The channel is clear when the RSSI is below the threshold minus the hysteresis
+    Energy = 0b01,
+    /// The channel is clear when no frame is being received
+    NotReceiving = 0b10,
+    /// The channel is clear when the RSSI is below the threshold minus the hysteresis and no
+    /// frame is being received (default)
+    EnergyAndNotReceiving = 0b11,
+}
+
+/// Configuration of the clear channel assessment, written to CCACTRL0 and CCACTRL1
+///
+/// The channel becomes busy when the RSSI reaches `threshold` and only becomes clear again
+/// when the RSSI drops below `threshold - hysteresis`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CcaConfig {
+    /// When the channel is clear
+    pub mode: CcaMode,
+    /// The energy threshold in dBm, from -201 to 54 dBm
+    pub threshold: i16,
+    /// The hysteresis in dB, from 0 to 7 dB
+    pub hysteresis: u8,
+}
+
+impl CcaConfig {
+    /// The threshold recommended by TI (-81 dBm), with the reset values of the mode and the
+    /// hysteresis, used by default
+    pub const fn standard() -> Self {
+        Self {
+            mode: CcaMode::EnergyAndNotReceiving,
+            threshold: -81,
+            hysteresis: 2,
+        }
+    }
+
+    /// Defer to weaker signals, for a channel shared with many other devices
+    ///
+    /// The threshold is -90 dBm with a hysteresis of 4 dB, and a frame being received also
+    /// makes the channel busy
+    pub const fn conservative() -> Self {
+        Self {
+            mode: CcaMode::EnergyAndNotReceiving,
+            threshold: -90,
+            hysteresis: 4,
+        }
+    }
+
+    /// Only defer to strong signals, for a noisy environment where a low threshold would keep
+    /// the channel busy
+    ///
+    /// The threshold is -70 dBm with a hysteresis of 2 dB, and frames being received are
+    /// ignored
+    pub const fn aggressive() -> Self {
+        Self {
+            mode: CcaMode::Energy,
+            threshold: -70,
+            hysteresis: 2,
+        }
+    }
+}
+
+impl Default for CcaConfig {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Requirements for the detection of the SFD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SfdDetection {
+    /// One zero symbol of the preamble must be above the correlation threshold (default)
+    Preamble,
+    /// One zero symbol of the preamble and both symbols of the SFD must be above the
+    /// correlation threshold
+    PreambleAndSfd,
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Channel {
@@ -165,6 +411,34 @@ pub(crate) const fn channel_freq_reg_val(channel: Channel) -> u32 {
     11 + 5 * (channel as u32 - 11)
 }
 
+/// The channel of a `FREQCTRL` value, `None` when the value is not an IEEE 802.15.4 channel
+#[inline]
+pub(crate) const fn channel_from_freq_reg_val(val: u32) -> Option<Channel> {
+    if val < 11 || !(val - 11).is_multiple_of(5) {
+        return None;
+    }
+
+    Some(match 11 + (val - 11) / 5 {
+        11 => Channel::Channel11,
+        12 => Channel::Channel12,
+        13 => Channel::Channel13,
+        14 => Channel::Channel14,
+        15 => Channel::Channel15,
+        16 => Channel::Channel16,
+        17 => Channel::Channel17,
+        18 => Channel::Channel18,
+        19 => Channel::Channel19,
+        20 => Channel::Channel20,
+        21 => Channel::Channel21,
+        22 => Channel::Channel22,
+        23 => Channel::Channel23,
+        24 => Channel::Channel24,
+        25 => Channel::Channel25,
+        26 => Channel::Channel26,
+        _ => return None,
+    })
+}
+
 /// Op Codes for the Command Strobe/CSMA-CA Processor
 #[repr(u8)]
 #[derive(Debug, Copy, Clone)]
@@ -263,9 +537,53 @@ pub enum CspOpCode {
     IsClear = 0xFF,
 }
 
+/// Compare registers of the MAC timer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacTimerCompare {
+    Compare1,
+    Compare2,
+}
+
+/// MAC timer events that can be routed to the `MT_EVENT1` and `MT_EVENT2` inputs of the CSP
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacTimerEvent {
+    /// The timer wrapped around at the end of its period
+    Period = 0b000,
+    /// The timer reached the value of the first compare register
+    Compare1 = 0b001,
+    /// The timer reached the value of the second compare register
+    Compare2 = 0b010,
+    /// The overflow counter wrapped around at the end of its period
+    OverflowPeriod = 0b011,
+    /// The overflow counter reached the value of the first overflow compare register
+    OverflowCompare1 = 0b100,
+    /// The overflow counter reached the value of the second overflow compare register
+    OverflowCompare2 = 0b101,
+    /// No event
+    None = 0b111,
+}
+
 pub struct RadioOn;
 pub struct RadioOff;
 
+/// A snapshot of the status registers of the radio, see [`RadioDriver::dump_status`]
+///
+/// The fields contain the raw values of the registers with the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadioStatus {
+    pub fsmstat0: u8,
+    pub fsmstat1: u8,
+    /// The raw RSSI value, a signed value that is 0x80 when it is not valid
+    pub rssi: u8,
+    pub rssistat: u8,
+    pub rfirqf0: u8,
+    pub rfirqf1: u8,
+    pub rferrf: u8,
+    pub rxfifocnt: u8,
+    pub txfifocnt: u8,
+}
+
 pub struct RadioDriver<'p, State> {
     _ffsm: PhantomData<&'p mut RfcoreFfsm>,
     _xreg: PhantomData<&'p mut RfcoreXreg>,
@@ -273,9 +591,21 @@ pub struct RadioDriver<'p, State> {
     _ana: PhantomData<&'p mut AnaRegs>,
     tx_channel: dma::Channel,
     rx_channel: dma::Channel,
+    /// The CRC OK bit of the footer of the last frame that was read
+    last_crc_ok: bool,
+    /// The DWT cycle count when the last transmission was started
+    tx_start: Option<u32>,
+    /// Drop frames with an incorrect CRC in [`RadioDriver::read`]
+    reject_bad_frames: bool,
+    /// Abort the auto ACK of broadcast frames in [`RadioDriver::handle_broadcast_ack`]
+    suppress_broadcast_ack: bool,
     _state: PhantomData<State>,
 }
 
+impl<State> InterruptExt for RadioDriver<'_, State> {
+    const INTERRUPT: Interrupt = Interrupt::RF_TXRX;
+}
+
 impl<State> RadioDriver<'_, State> {
     #[inline]
     fn ffsm_regs() -> &'static rfcore_ffsm::RegisterBlock {
@@ -305,14 +635,15 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| unsafe { w.bits(id & 0xFF) });
         Self::ffsm_regs()
             .pan_id1()
-            .modify(|_, w| unsafe { w.bits(id >> 8) });
+            .modify(|_, w| unsafe { w.bits((id >> 8) & 0xFF) });
     }
 
     /// Return the PAN ID that is currently used
     #[inline]
     pub fn get_pan_id(&mut self) -> u16 {
-        (Self::ffsm_regs().pan_id1().read().bits() << 8) as u16
-            | (Self::ffsm_regs().pan_id0().read().bits() & 0xFF) as u16
+        let high = (Self::ffsm_regs().pan_id1().read().bits() & 0xFF) as u16;
+        let low = (Self::ffsm_regs().pan_id0().read().bits() & 0xFF) as u16;
+        (high << 8) | low
     }
 
     /// Set the short address
@@ -323,14 +654,15 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| unsafe { w.bits(addr as u32 & 0xFF) });
         Self::ffsm_regs()
             .short_addr1()
-            .modify(|_, w| unsafe { w.bits(addr as u32 >> 8) });
+            .modify(|_, w| unsafe { w.bits((addr as u32 >> 8) & 0xFF) });
     }
 
     /// Return the short address
     #[inline]
     pub fn get_short_address(&mut self) -> u16 {
-        (Self::ffsm_regs().short_addr1().read().bits() << 8) as u16
-            | (Self::ffsm_regs().short_addr0().read().bits() & 0xFF) as u16
+        let high = (Self::ffsm_regs().short_addr1().read().bits() & 0xFF) as u16;
+        let low = (Self::ffsm_regs().short_addr0().read().bits() & 0xFF) as u16;
+        (high << 8) | low
     }
 
     /// Set the extended address
@@ -355,19 +687,126 @@ impl<State> RadioDriver<'_, State> {
             .write(|w| unsafe { w.ext_addr7().bits(addr[0]) });
     }
 
+    /// Use the IEEE address burned in at the factory as extended address, returning it
+    ///
+    /// The address is read with [`crate::get_ieee_address`], most significant byte first, which
+    /// is the order [`Self::set_extended_address`] expects. When `set_short` is set, the short
+    /// address is derived from the two least significant bytes of the extended address. Such a
+    /// short address is not guaranteed to be unique in the PAN, and is not set when it is one
+    /// of the reserved addresses 0xFFFE or 0xFFFF.
+    pub fn use_factory_extended_address(&mut self, set_short: bool) -> [u8; 8] {
+        let mut addr = [0u8; 8];
+        crate::get_ieee_address(&mut addr);
+
+        self.set_extended_address(&addr);
+
+        if set_short {
+            let short = u16::from_be_bytes([addr[6], addr[7]]);
+            if short != NO_SHORT_ADDR && short != BROADCAST_ADDR {
+                self.set_short_address(short);
+            }
+        }
+
+        addr
+    }
+
+    /// Configure the mode, threshold and hysteresis of the clear channel assessment
+    ///
+    /// The threshold is clamped to -201..=54 dBm and the hysteresis to 7 dB
+    #[inline]
+    pub fn set_cca_config(&mut self, config: &CcaConfig) {
+        // CCA_THR is a signed value with an offset of 73 dB.
+        let cca_thr = config
+            .threshold
+            .saturating_add(73)
+            .clamp(i8::MIN as i16, i8::MAX as i16) as i8 as u8;
+        let xreg = Self::xreg_regs();
+        xreg.ccactrl0()
+            .modify(|_, w| unsafe { w.cca_thr().bits(cca_thr) });
+        xreg.ccactrl1().modify(|_, w| unsafe {
+            w.cca_mode()
+                .bits(config.mode as u8)
+                .cca_hyst()
+                .bits(config.hysteresis.min(7))
+        });
+    }
+
+    /// Return the configuration of the clear channel assessment
+    #[inline]
+    pub fn cca_config(&self) -> CcaConfig {
+        let xreg = Self::xreg_regs();
+        let cca_thr = xreg.ccactrl0().read().cca_thr().bits() as i8;
+        let ccactrl1 = xreg.ccactrl1().read();
+
+        CcaConfig {
+            mode: match ccactrl1.cca_mode().bits() {
+                0b00 => CcaMode::AlwaysClear,
+                0b01 => CcaMode::Energy,
+                0b10 => CcaMode::NotReceiving,
+                _ => CcaMode::EnergyAndNotReceiving,
+            },
+            threshold: cca_thr as i16 - 73,
+            hysteresis: ccactrl1.cca_hyst().bits(),
+        }
+    }
+
+    /// Returns the channel the radio is tuned to
+    ///
+    /// Returns `None` when `FREQCTRL` holds a frequency in between two IEEE 802.15.4 channels,
+    /// use [`RadioDriver::current_frequency`] to get the frequency in that case
+    #[inline]
+    pub fn current_channel(&self) -> Option<Channel> {
+        channel_from_freq_reg_val(Self::xreg_regs().freqctrl().read().freq().bits() as u32)
+    }
+
+    /// Returns the frequency the radio is tuned to, in Hz
+    ///
+    /// The frequency is `2394 + FREQCTRL` MHz, which also holds for values that are not a
+    /// channel
+    #[inline]
+    pub fn current_frequency(&self) -> u32 {
+        (2394 + Self::xreg_regs().freqctrl().read().freq().bits() as u32) * 1_000_000
+    }
+
     /// Return the CCA threshold in dB
     #[inline]
     pub fn get_cca_threshold(&mut self) -> i32 {
-        let cca_thr = Self::xreg_regs().ccactrl0().read().cca_thr().bits() as i32;
-        cca_thr - 73
+        self.cca_config().threshold as i32
     }
 
     /// Set the CCA threshold in dB
+    ///
+    /// The threshold is clamped to the range of the signed CCA_THR field (-201 to 54 dB). The
+    /// mode and the hysteresis are kept, see [`RadioDriver::set_cca_config`]
     #[inline]
     pub fn set_cca_threshold(&mut self, threshold: i32) {
+        let config = CcaConfig {
+            threshold: threshold.clamp(-201, 54) as i16,
+            ..self.cca_config()
+        };
+        self.set_cca_config(&config);
+    }
+
+    /// Set the amount of bytes in the RX FIFO at which FIFOP goes high
+    ///
+    /// By default this is `MAX_PACKET_LEN`, such that FIFOP only goes high when a complete frame
+    /// is received. With a lower threshold, [`RadioDriver::received_packet`] and the FIFOP
+    /// interrupt already fire when more than `bytes` bytes of a frame are in the RX FIFO, which
+    /// allows to inspect the header before the frame is complete. FIFOP still goes high at the
+    /// end of a frame shorter than the threshold. [`RadioDriver::read`] expects the complete
+    /// frame to be in the RX FIFO, so it must not be called before the rest of the frame is
+    /// received. The threshold must fit in the 7-bit FIFOP_THR field
+    #[inline]
+    pub fn set_fifop_threshold(&mut self, bytes: u8) -> Result<(), RadioError> {
+        if bytes > 0x7f {
+            return Err(RadioError::InvalidConfig);
+        }
+
         Self::xreg_regs()
-            .ccactrl0()
-            .modify(|_, w| unsafe { w.bits((threshold + 73) as u32) });
+            .fifopctrl()
+            .modify(|_, w| unsafe { w.fifop_thr().bits(bytes) });
+
+        Ok(())
     }
 
     /// Return the TX power in dB
@@ -396,6 +835,40 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| w.frame_filter_en().clear_bit());
     }
 
+    /// Only deliver frames that pass frame filtering and have a correct CRC
+    ///
+    /// Rejecting enables frame filtering (`FRMFILT0.FRAME_FILTER_EN`) and auto CRC
+    /// (`FRMCTRL0.AUTOCRC`). Frames that fail frame filtering (wrong frame type, PAN ID or
+    /// destination address) are flushed from the RX FIFO by the radio itself, and do not raise
+    /// RxPktDone or FIFOP once they are rejected. The radio can not reject frames with an
+    /// incorrect CRC, since the CRC is only known after the frame is in the RX FIFO, so these
+    /// still raise the interrupts. [`RadioDriver::read`] then drops them and returns 0.
+    ///
+    /// Not rejecting disables frame filtering again, auto CRC is left enabled
+    #[inline]
+    pub fn reject_bad_frames(&mut self, reject: bool) {
+        if reject {
+            self.enable_frame_filtering();
+            self.enable_autocrc();
+        } else {
+            self.disable_frame_filtering();
+        }
+
+        self.reject_bad_frames = reject;
+    }
+
+    /// Do not acknowledge broadcast frames that request an ACK
+    ///
+    /// Auto ACK acknowledges every accepted frame with the ACK request bit set, also when it is
+    /// sent to the broadcast address, which IEEE 802.15.4 does not allow. The radio can not
+    /// filter these frames itself: with suppression enabled,
+    /// [`RadioDriver::handle_broadcast_ack`] must be called from the radio interrupt handler
+    /// to abort the ACK
+    #[inline]
+    pub fn suppress_broadcast_ack(&mut self, suppress: bool) {
+        self.suppress_broadcast_ack = suppress;
+    }
+
     /// Enable SHR search
     #[inline]
     pub fn enable_shr_search(&mut self) {
@@ -412,22 +885,71 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| unsafe { w.rx_mode().bits(0b11) });
     }
 
+    /// Set the number of preamble bytes sent before the SFD
+    ///
+    /// The default of 4 bytes is compliant with IEEE 802.15.4. Any other length breaks the
+    /// interoperability with standard devices, so only change it to talk to a non-standard
+    /// peer. The preamble can be 2 to 17 bytes long, other lengths return
+    /// [`RadioError::InvalidConfig`]
+    #[inline]
+    pub fn set_preamble_length(&mut self, bytes: u8) -> Result<(), RadioError> {
+        if !(2..=17).contains(&bytes) {
+            return Err(RadioError::InvalidConfig);
+        }
+
+        Self::xreg_regs()
+            .mdmctrl0()
+            .modify(|_, w| unsafe { w.preamble_length().bits(bytes - 2) });
+
+        Ok(())
+    }
+
+    /// Set the requirements for the detection of the SFD
+    ///
+    /// The SFD itself (0xA7) is fixed in hardware and can not be changed. Requiring the SFD
+    /// symbols to be above the correlation threshold lowers the number of frames found on
+    /// noise, at the cost of sensitivity. The default is [`SfdDetection::Preamble`], other
+    /// settings might not find the frames of standard devices
+    #[inline]
+    pub fn set_sfd_detection(&mut self, detection: SfdDetection) {
+        Self::xreg_regs().mdmctrl1().modify(|_, w| {
+            w.corr_thr_sfd()
+                .bit(detection == SfdDetection::PreambleAndSfd)
+        });
+    }
+
     /// Enable auto CRC
+    ///
+    /// This is the default: the radio appends the 2-byte checksum to every frame that is sent,
+    /// and checks it for every frame that is received
     #[inline]
-    fn enable_autocrc(&mut self) {
+    pub fn enable_autocrc(&mut self) {
         Self::xreg_regs()
             .frmctrl0()
             .modify(|_, w| w.autocrc().set_bit());
     }
 
     /// Disable auto CRC
+    ///
+    /// This changes the length accounting of [`RadioDriver::prepare`]: no checksum bytes are
+    /// reserved, and the payload is sent as is. The payload must then end with its own 2-byte
+    /// checksum (possibly a wrong one, e.g. for conformance testing), and can be
+    /// `MAX_PACKET_LEN` (127) bytes long. Received frames are not checked either: the raw
+    /// checksum is returned as the last two bytes by [`RadioDriver::read`], and
+    /// [`RadioDriver::last_frame_crc_ok`] always returns `false`
     #[inline]
-    fn disable_autocrc(&mut self) {
+    pub fn disable_autocrc(&mut self) {
         Self::xreg_regs()
             .frmctrl0()
             .modify(|_, w| w.autocrc().clear_bit());
     }
 
+    /// Check if auto CRC is enabled
+    #[inline]
+    pub fn is_autocrc_enabled(&self) -> bool {
+        Self::xreg_regs().frmctrl0().read().autocrc().bit_is_set()
+    }
+
     /// Enable auto ACK
     #[inline]
     fn enable_autoack(&mut self) {
@@ -444,8 +966,128 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| w.autoack().clear_bit());
     }
 
+    /// Start the MAC timer.
+    ///
+    /// The MAC timer runs at 32 MHz and is synchronised with the symbol clock of the radio. One
+    /// IEEE 802.15.4 symbol period (16 µs) equals 512 ticks. The 16-bit timer wraps around every
+    /// 2^16 ticks (2.048 ms), after which the 24-bit overflow counter is incremented.
+    #[inline]
+    pub fn start_mac_timer(&mut self) {
+        // sfr.mtctrl.write(|w| w.sync().set_bit().run().set_bit());
+        Self::sfr_regs().mtctrl().write(|w| w.sync().set_bit());
+        Self::sfr_regs().mtctrl().write(|w| w.run().set_bit());
+
+        while Self::sfr_regs().mtctrl().read().state().bit_is_clear() {}
+
+        // Reading MTM0 latches MTM1 and the overflow counter, such that they are read
+        // atomically.
+        Self::sfr_regs()
+            .mtctrl()
+            .modify(|_, w| w.latch_mode().set_bit());
+
+        // XXX: Contiki-ng does the following:
+        // First, the timer is started, then ended and then started again.
+        // I'm not sure why they do that.
+    }
+
+    /// Check if the MAC timer is running
+    #[inline]
+    fn is_mac_timer_running() -> bool {
+        Self::sfr_regs().mtctrl().read().state().bit_is_set()
+    }
+
+    /// Read the MAC timer register selected with `sel` and the overflow register selected with
+    /// `ovf_sel`, combined into a single 32-bit value.
+    #[inline]
+    fn read_mac_timer(&self, sel: u8, ovf_sel: u8) -> u32 {
+        let sfr = Self::sfr_regs();
+
+        cortex_m::interrupt::free(|_| {
+            sfr.mtmsel()
+                .write(|w| unsafe { w.mtmsel().bits(sel).mtmovfsel().bits(ovf_sel) });
+
+            // MTM0 has to be read first, this latches the other registers.
+            let mut val = sfr.mtm0().read().bits() & 0xff;
+            val |= (sfr.mtm1().read().bits() & 0xff) << 8;
+            val |= (sfr.mtmovf0().read().bits() & 0xff) << 16;
+            val |= (sfr.mtmovf1().read().bits() & 0xff) << 24;
+            val
+        })
+    }
+
+    /// Return the current value of the MAC timer.
+    ///
+    /// The lower 16 bits are the value of the timer, the upper 16 bits are the lower bits of
+    /// the overflow counter. This only counts linearly when the period of the timer is not
+    /// changed (the default period is 2^16 ticks).
+    #[inline]
+    pub fn mac_timer_now(&self) -> u32 {
+        self.read_mac_timer(0b000, 0b000)
+    }
+
+    /// Return the value of the MAC timer when the last SFD was received or transmitted.
+    ///
+    /// The MAC timer captures its value on every SFD event, so the timestamp is in the same
+    /// format as [`RadioDriver::mac_timer_now`].
+    #[inline]
     pub fn get_sfd_timestamp(&mut self) -> u32 {
-        todo!();
+        self.read_mac_timer(0b001, 0b001)
+    }
+
+    /// Set a compare value of the MAC timer.
+    ///
+    /// The compare event fires when the 16-bit timer reaches `value`. Use
+    /// [`RadioDriver::set_mac_timer_event1`] or [`RadioDriver::set_mac_timer_event2`] to let the
+    /// CSP wait on it with [`CspOpCode::WEvent1`] or [`CspOpCode::WEvent2`].
+    #[inline]
+    pub fn set_mac_timer_compare(&mut self, compare: MacTimerCompare, value: u16) {
+        let sfr = Self::sfr_regs();
+        let sel = match compare {
+            MacTimerCompare::Compare1 => 0b011,
+            MacTimerCompare::Compare2 => 0b100,
+        };
+
+        cortex_m::interrupt::free(|_| {
+            sfr.mtmsel().modify(|_, w| unsafe { w.mtmsel().bits(sel) });
+            sfr.mtm0().write(|w| unsafe { w.bits(value as u32 & 0xff) });
+            sfr.mtm1()
+                .write(|w| unsafe { w.bits((value as u32 >> 8) & 0xff) });
+        });
+    }
+
+    /// Select the MAC timer event that generates the `MT_EVENT1` pulse for the CSP.
+    #[inline]
+    pub fn set_mac_timer_event1(&mut self, event: MacTimerEvent) {
+        Self::sfr_regs()
+            .mtcspcfg()
+            .modify(|_, w| unsafe { w.mactimer_event1_cfg().bits(event as u8) });
+    }
+
+    /// Select the MAC timer event that generates the `MT_EVENT2` pulse for the CSP.
+    #[inline]
+    pub fn set_mac_timer_event2(&mut self, event: MacTimerEvent) {
+        Self::sfr_regs()
+            .mtcspcfg()
+            .modify(|_, w| unsafe { w.mactimer_evenmt_cfg().bits(event as u8) });
+    }
+
+    /// Check if a compare event of the MAC timer is pending
+    #[inline]
+    pub fn is_mac_timer_compare_pending(&self, compare: MacTimerCompare) -> bool {
+        let mtirqf = Self::sfr_regs().mtirqf().read();
+        match compare {
+            MacTimerCompare::Compare1 => mtirqf.mactimer_compare1f().bit_is_set(),
+            MacTimerCompare::Compare2 => mtirqf.mactimer_compare2f().bit_is_set(),
+        }
+    }
+
+    /// Clear a compare event of the MAC timer
+    #[inline]
+    pub fn clear_mac_timer_compare(&mut self, compare: MacTimerCompare) {
+        Self::sfr_regs().mtirqf().modify(|_, w| match compare {
+            MacTimerCompare::Compare1 => w.mactimer_compare1f().clear_bit(),
+            MacTimerCompare::Compare2 => w.mactimer_compare2f().clear_bit(),
+        });
     }
 
     /// Set the RX mode
@@ -464,9 +1106,99 @@ impl<State> RadioDriver<'_, State> {
             .modify(|_, w| unsafe { w.instr().bits(op_code as u8) });
     }
 
+    /// Capture the status registers of the radio
+    ///
+    /// The registers are read in a critical section, such that the snapshot is not interrupted
+    /// by an interrupt handler that changes the state of the radio
+    pub fn dump_status(&self) -> RadioStatus {
+        let xreg = Self::xreg_regs();
+        let sfr = Self::sfr_regs();
+
+        cortex_m::interrupt::free(|_| RadioStatus {
+            fsmstat0: xreg.fsmstat0().read().bits() as u8,
+            fsmstat1: xreg.fsmstat1().read().bits() as u8,
+            rssi: xreg.rssi().read().bits() as u8,
+            rssistat: xreg.rssistat().read().bits() as u8,
+            rfirqf0: sfr.rfirqf0().read().bits() as u8,
+            rfirqf1: sfr.rfirqf1().read().bits() as u8,
+            rferrf: sfr.rferrf().read().bits() as u8,
+            rxfifocnt: xreg.rxfifocnt().read().bits() as u8,
+            txfifocnt: xreg.txfifocnt().read().bits() as u8,
+        })
+    }
+
+    /// Apply the changes from the default values of the tuning registers
+    #[inline]
+    fn apply_tuning() {
+        let xreg = Self::xreg_regs();
+        let ana = Self::ana_regs();
+
+        // These are changes from the default values (following contiki-ng)
+        xreg.txfiltcfg().modify(|_, w| unsafe { w.bits(0x09) }); // TX anti-aliasing filter bandwidth
+        xreg.agcctrl1().modify(|_, w| unsafe { w.bits(0x15) }); // AGC target value
+        ana.ivctrl().modify(|_, w| unsafe { w.bits(0x0B) }); // ANA bias current
+        xreg.fscal1().modify(|_, w| unsafe { w.bits(0x01) }); // Tune frequency calibration
+    }
+
+    /// Flush the RX FIFO
+    ///
+    /// All bytes in the RX FIFO are discarded, including a frame that is partially read or
+    /// still being received
+    #[inline]
+    pub fn flush_rx(&mut self) {
+        // The flush is done twice, following contiki-ng, such that the FIFOP and FIFO
+        // signals are reset as well.
+        self.send_csp_op_code(CspOpCode::IsFlushRx);
+        self.send_csp_op_code(CspOpCode::IsFlushRx);
+    }
+
+    /// Set a bit in the RX enable mask (RXENABLE)
+    ///
+    /// The receiver is enabled as long as any bit in the mask is set. Bit 7 is set by
+    /// [`CspOpCode::SRXOn`], bit 6 on TX (when configured) and bit 5 by
+    /// [`CspOpCode::SRXMaskBitSet`]. The other bits are free to use by software, e.g. to gate
+    /// RX between CSP waits.
+    ///
+    /// Unlike [`RadioDriver::enable`] and [`RadioDriver::disable`], this does not change the
+    /// state of the driver and does not turn off the frequency synthesizer. The radio goes back
+    /// to idle once all bits are cleared, but is turned back on by setting a single bit again.
+    #[inline]
+    pub fn rx_enable_set(&mut self, bit: u8) {
+        assert!(bit < 8);
+        Self::xreg_regs()
+            .rxmaskset()
+            .write(|w| unsafe { w.rxenmaskset().bits(1 << bit) });
+    }
+
+    /// Clear a bit in the RX enable mask (RXENABLE)
+    ///
+    /// See [`RadioDriver::rx_enable_set`].
+    #[inline]
+    pub fn rx_enable_clear(&mut self, bit: u8) {
+        assert!(bit < 8);
+        Self::xreg_regs()
+            .rxmaskclr()
+            .write(|w| unsafe { w.rxenmaskclr().bits(1 << bit) });
+    }
+
+    /// Return `true` when any bit of the RX enable mask (RXENABLE) is set
+    #[inline]
+    pub fn rx_enabled(&self) -> bool {
+        Self::xreg_regs().rxenable().read().rxenmask().bits() != 0
+    }
+
     /// Listen to an interrupt
+    ///
+    /// The `RF_TXRX` interrupt is unmasked in the NVIC as well
     #[inline]
     pub fn listen(&mut self, event: Event) {
+        self.enable_event(event);
+        self.unmask_interrupt();
+    }
+
+    /// Enable an interrupt in the interrupt mask registers, without unmasking `RF_TXRX`
+    #[inline]
+    fn enable_event(&mut self, event: Event) {
         match event {
             Event::Sfd
             | Event::Fifop
@@ -501,6 +1233,8 @@ impl<State> RadioDriver<'_, State> {
     }
 
     /// Unlisten to an interrupt
+    ///
+    /// The `RF_TXRX` interrupt is masked in the NVIC when no interrupt is listened to anymore
     #[inline]
     pub fn unlisten(&mut self, event: Event) {
         match event {
@@ -530,6 +1264,12 @@ impl<State> RadioDriver<'_, State> {
                 Self::xreg_regs().rfirqm1().write(|w| unsafe { w.bits(0) });
             }
         };
+
+        if Self::xreg_regs().rfirqm0().read().bits() == 0
+            && Self::xreg_regs().rfirqm1().read().bits() == 0
+        {
+            self.mask_interrupt();
+        }
     }
 
     /// Clear an interrupt
@@ -590,19 +1330,44 @@ impl<State> RadioDriver<'_, State> {
     }
 
     /// Listen to a specific error interrupt
+    ///
+    /// The `RF_ERROR` interrupt is unmasked in the NVIC as well
     #[inline]
     pub fn listen_error(&mut self, event: ErrorEvent) {
         Self::xreg_regs()
             .rferrm()
             .modify(|r, w| unsafe { w.bits(r.bits() | event.mask()) });
+        unsafe { NVIC::unmask(Interrupt::RF_ERROR) };
     }
 
     /// Unlisten to a specific error interrupt
+    ///
+    /// The `RF_ERROR` interrupt is masked in the NVIC when no error interrupt is listened to
+    /// anymore
     #[inline]
     pub fn unlisten_error(&mut self, event: ErrorEvent) {
         Self::xreg_regs()
             .rferrm()
             .modify(|r, w| unsafe { w.bits(r.bits() & !event.mask()) });
+
+        if Self::xreg_regs().rferrm().read().bits() == 0 {
+            NVIC::mask(Interrupt::RF_ERROR);
+        }
+    }
+
+    /// Only listen to the error interrupts in `events`
+    ///
+    /// The error interrupts that are not in `events` are disabled, an empty slice disables all
+    /// of them. Under heavy traffic, errors like [`ErrorEvent::RxOverf`] can be handled by
+    /// polling [`RadioDriver::is_error_interrupt`] instead, since the error flags are set
+    /// whether the interrupt is enabled or not. The error interrupts enabled by
+    /// [`RadioDriver::enable`] are set with [`EnableConfig::error_events`]
+    #[inline]
+    pub fn set_error_interrupts(&mut self, events: &[ErrorEvent]) {
+        let mask = events.iter().fold(0, |mask, event| mask | event.mask()) & 0x7F;
+        Self::xreg_regs()
+            .rferrm()
+            .write(|w| unsafe { w.bits(mask) });
     }
 
     /// Clear a specific error interrupt
@@ -645,22 +1410,58 @@ impl<'p> RadioDriver<'p, RadioOff> {
             _ana: PhantomData,
             tx_channel,
             rx_channel,
+            last_crc_ok: false,
+            tx_start: None,
+            reject_bad_frames: false,
+            suppress_broadcast_ack: false,
             _state: PhantomData,
         }
     }
 
+    /// Check if the clock of the RF core is enabled in active mode
+    ///
+    /// Without the clock, writes to the radio registers are ignored and reads return zero, so the
+    /// radio never works. Use [`crate::sys_ctrl::SysCtrl::enable_radio_in_active_mode`] to enable
+    /// the clock
+    #[inline]
+    pub fn is_clocked(&self) -> bool {
+        crate::sys_ctrl::is_radio_clock_enabled()
+    }
+
     /// Enable the radio module
     ///
+    /// This actually flushes RX and enables RX. The interrupts of [`EnableConfig::default`] are
+    /// enabled, which includes all error interrupts. Use [`RadioDriver::enable_with`] to leave
+    /// out (some of) the error interrupts.
+    ///
+    /// Returns [`RadioError::NotClocked`] when the clock of the RF core is not enabled
+    #[inline]
+    pub fn enable(
+        self,
+        config: Option<RadioConfig>,
+    ) -> Result<RadioDriver<'p, RadioOn>, RadioError> {
+        self.enable_with(config, &EnableConfig::default())
+    }
+
+    /// Enable the radio module, only enabling the interrupts of `enable_config`
+    ///
     /// This actually flushes RX and enables RX.
+    ///
+    /// Returns [`RadioError::NotClocked`] when the clock of the RF core is not enabled, without
+    /// touching the radio registers
     #[inline]
-    pub fn enable(mut self, config: Option<RadioConfig>) -> RadioDriver<'p, RadioOn> {
-        // NOTE Maybe we can check here if the clock for RF is enabled
+    pub fn enable_with(
+        mut self,
+        config: Option<RadioConfig>,
+        enable_config: &EnableConfig,
+    ) -> Result<RadioDriver<'p, RadioOn>, RadioError> {
+        if !self.is_clocked() {
+            return Err(RadioError::NotClocked);
+        }
 
         let xreg = Self::xreg_regs();
-        let ana = Self::ana_regs();
 
-        xreg.ccactrl0()
-            .modify(|_, w| unsafe { w.cca_thr().bits(CCA_THRES as u8) });
+        self.set_cca_config(&enable_config.cca);
 
         if let Some(config) = config {
             self.set_pan_id(config.dst_pan_id);
@@ -670,11 +1471,7 @@ impl<'p> RadioDriver<'p, RadioOff> {
 
         self.send_csp_op_code(CspOpCode::IsFlushRx);
 
-        // These are changes from the default values (following contiki-ng)
-        xreg.txfiltcfg().modify(|_, w| unsafe { w.bits(0x09) }); // TX anti-aliasing filter bandwidth
-        xreg.agcctrl1().modify(|_, w| unsafe { w.bits(0x15) }); // AGC target value
-        ana.ivctrl().modify(|_, w| unsafe { w.bits(0x0B) }); // ANA bias current
-        xreg.fscal1().modify(|_, w| unsafe { w.bits(0x01) }); // Tune frequency calibration
+        Self::apply_tuning();
 
         self.enable_autocrc();
         self.enable_autoack();
@@ -693,50 +1490,43 @@ impl<'p> RadioDriver<'p, RadioOff> {
         // Enable TX DMA mode
         // Disable peripheral requests
         self.tx_channel.allow_periph_requests(false);
-        self.tx_channel
-            .set_destination_end_address(Self::sfr_regs().rfdata().as_ptr() as u32);
-
-        self.tx_channel
-            .set_arbitration_size(dma::Arbitration::Transfer128);
-        self.tx_channel
-            .set_transfer_mode(dma::TransferMode::AutoRequest);
-        self.tx_channel.set_source_size(dma::DataSize::Data8bit);
-        self.tx_channel
-            .set_destination_size(dma::DataSize::Data8bit);
-        self.tx_channel
-            .set_source_increment(dma::AddressIncrement::Increment8bit);
-        self.tx_channel
-            .set_destination_increment(dma::AddressIncrement::None);
+        self.tx_channel.configure(|c| {
+            c.set_destination_end_address(Self::sfr_regs().rfdata().as_ptr() as u32)
+                .set_arbitration_size(dma::Arbitration::Transfer128)
+                .set_transfer_mode(dma::TransferMode::AutoRequest)
+                .set_source_size(dma::DataSize::Data8bit)
+                .set_destination_size(dma::DataSize::Data8bit)
+                .set_source_increment(dma::AddressIncrement::Increment8bit)
+                .set_destination_increment(dma::AddressIncrement::None);
+        });
 
         // enable rx dma mode
         // disable peripheral requests
         self.rx_channel.allow_periph_requests(true);
-        self.rx_channel
-            .set_source_end_address(Self::sfr_regs().rfdata().as_ptr() as u32);
-
-        self.rx_channel
-            .set_arbitration_size(dma::Arbitration::Transfer128);
-        self.rx_channel
-            .set_transfer_mode(dma::TransferMode::AutoRequest);
-        self.rx_channel.set_source_size(dma::DataSize::Data8bit);
-        self.rx_channel
-            .set_destination_size(dma::DataSize::Data8bit);
-        self.rx_channel
-            .set_source_increment(dma::AddressIncrement::None);
-        self.rx_channel
-            .set_destination_increment(dma::AddressIncrement::Increment8bit);
+        self.rx_channel.configure(|c| {
+            c.set_source_end_address(Self::sfr_regs().rfdata().as_ptr() as u32)
+                .set_arbitration_size(dma::Arbitration::Transfer128)
+                .set_transfer_mode(dma::TransferMode::AutoRequest)
+                .set_source_size(dma::DataSize::Data8bit)
+                .set_destination_size(dma::DataSize::Data8bit)
+                .set_source_increment(dma::AddressIncrement::None)
+                .set_destination_increment(dma::AddressIncrement::Increment8bit);
+        });
 
         self.clear_event(Event::All);
         self.clear_err(ErrorEvent::All);
 
-        // Enable RX interrupts
-        self.listen(Event::Fifop);
-        self.listen(Event::TxDone);
-        self.listen_error(ErrorEvent::All);
+        // Enable the requested interrupts
+        for event in enable_config.events {
+            self.enable_event(*event);
+        }
+        self.set_error_interrupts(enable_config.error_events);
 
-        unsafe { NVIC::unmask(Interrupt::RF_TXRX) };
+        if enable_config.unmask_interrupt {
+            self.unmask_interrupt();
+        }
 
-        self.enable_rx()
+        Ok(self.enable_rx())
     }
 
     /// Set the channel
@@ -778,24 +1568,14 @@ impl<'p> RadioDriver<'p, RadioOff> {
             _ana: PhantomData,
             tx_channel: self.tx_channel,
             rx_channel: self.rx_channel,
+            last_crc_ok: self.last_crc_ok,
+            tx_start: self.tx_start,
+            reject_bad_frames: self.reject_bad_frames,
+            suppress_broadcast_ack: self.suppress_broadcast_ack,
             _state: PhantomData,
         }
     }
 
-    /// Enable the MAC timer.
-    #[inline]
-    fn start_mac_timer(&mut self) {
-        // sfr.mtctrl.write(|w| w.sync().set_bit().run().set_bit());
-        Self::sfr_regs().mtctrl().write(|w| w.sync().set_bit());
-        Self::sfr_regs().mtctrl().write(|w| w.run().set_bit());
-
-        while Self::sfr_regs().mtctrl().read().state().bit_is_clear() {}
-
-        // XXX: Contiki-ng does the following:
-        // First, the timer is started, then ended and then started again.
-        // I'm not sure why they do that.
-    }
-
     #[inline]
     fn set_poll_mode(&mut self) {
         self.start_mac_timer();
@@ -814,6 +1594,77 @@ impl<'p> RadioDriver<'p, RadioOn> {
         self.disable_rx()
     }
 
+    /// Apply a new configuration to the running radio
+    ///
+    /// The PAN ID, short address, extended address and channel are applied together, with RX
+    /// stopped while they are written. A new channel only takes effect when RX is restarted,
+    /// the PAN ID and addresses are used immediately by the frame filtering. Stopping RX makes
+    /// sure no frame is filtered with a partially written configuration. A frame that is being
+    /// received during the update is dropped.
+    pub fn reconfigure(&mut self, config: &RadioConfig) -> Result<(), RadioError> {
+        config.validate()?;
+
+        // Wait for ongoing TX to complete
+        while Self::xreg_regs().fsmstat1().read().tx_active().bit_is_set() {}
+
+        cortex_m::interrupt::free(|_| {
+            self.send_csp_op_code(CspOpCode::IsRFOff);
+
+            self.set_pan_id(config.dst_pan_id);
+            self.set_short_address(config.short_addr);
+            self.set_extended_address(&config.ext_addr);
+            Self::xreg_regs()
+                .freqctrl()
+                .modify(|_, w| unsafe { w.bits(channel_freq_reg_val(config.channel)) });
+
+            self.send_csp_op_code(CspOpCode::IsFlushRx);
+            self.send_csp_op_code(CspOpCode::IsRXon);
+        });
+
+        Ok(())
+    }
+
+    /// Flush both FIFOs and return to a clean RX state, e.g. after an error
+    ///
+    /// The radio is turned off, which aborts an ongoing transmission or reception, and both
+    /// FIFOs are flushed. All pending events and errors are cleared, after which RX is turned
+    /// on again. The channel, the addresses and the other configuration are not changed. This
+    /// can be called from the ISR, e.g. on [`ErrorEvent::StrobeErr`].
+    pub fn reset_fifos(&mut self) {
+        cortex_m::interrupt::free(|_| {
+            self.send_csp_op_code(CspOpCode::IsRFOff);
+
+            self.flush_rx();
+            self.send_csp_op_code(CspOpCode::IsFlushTX);
+
+            self.clear_event(Event::All);
+            self.clear_err(ErrorEvent::All);
+            self.tx_start = None;
+
+            self.send_csp_op_code(CspOpCode::IsRXon);
+        });
+    }
+
+    /// Recalibrate the frequency synthesizer, e.g. after a large change in temperature
+    ///
+    /// The radio is turned off, the tuning registers are applied again and RX is turned on,
+    /// which calibrates the frequency synthesizer. This returns when the calibration is done.
+    /// The radio can not receive or transmit for about 192 us (12 symbol periods), and the
+    /// RX FIFO is flushed.
+    pub fn recalibrate(&mut self) {
+        // Wait for ongoing TX to complete
+        while Self::xreg_regs().fsmstat1().read().tx_active().bit_is_set() {}
+
+        cortex_m::interrupt::free(|_| {
+            self.send_csp_op_code(CspOpCode::IsRFOff);
+            Self::apply_tuning();
+            self.flush_rx();
+            self.send_csp_op_code(CspOpCode::IsRXon);
+        });
+
+        while !Self::xreg_regs().fsmstat0().read().cal_done().bit_is_set() {}
+    }
+
     #[inline]
     fn enable_tx(self) {
         // We can only enable TX when RX is on.
@@ -834,14 +1685,36 @@ impl<'p> RadioDriver<'p, RadioOn> {
             _ana: PhantomData,
             tx_channel: self.tx_channel,
             rx_channel: self.rx_channel,
+            last_crc_ok: self.last_crc_ok,
+            tx_start: self.tx_start,
+            reject_bad_frames: self.reject_bad_frames,
+            suppress_broadcast_ack: self.suppress_broadcast_ack,
             _state: PhantomData,
         }
     }
 
     /// Prepare the radio with a packet to be sent
+    ///
+    /// With auto CRC enabled, the radio appends the checksum and `payload` can be
+    /// `MAX_PAYLOAD_LEN` (125) bytes long. With auto CRC disabled, `payload` is sent exactly as
+    /// given and must include the checksum, see [`RadioDriver::disable_autocrc`]
+    ///
+    /// An empty payload is rejected with [`RadioError::IncorrectFrame`]: the frame would only
+    /// hold the checksum (or nothing at all), which no receiver accepts as a frame. Returns
+    /// [`RadioError::PayloadTooBig`] when the frame does not fit in a PHY packet
     #[inline]
     pub fn prepare(&mut self, payload: &[u8]) -> Result<(), RadioError> {
-        if payload.len() > MAX_PAYLOAD_LEN {
+        if payload.is_empty() {
+            return Err(RadioError::IncorrectFrame);
+        }
+
+        let checksum_len = if self.is_autocrc_enabled() {
+            CHECKSUM_LEN
+        } else {
+            0
+        };
+
+        if payload.len() + checksum_len > MAX_PACKET_LEN {
             return Err(RadioError::PayloadTooBig);
         }
 
@@ -854,7 +1727,7 @@ impl<'p> RadioDriver<'p, RadioOn> {
         // Write how much data is going to be send
         Self::sfr_regs()
             .rfdata()
-            .write(|w| unsafe { w.bits((payload.len() + CHECKSUM_LEN) as u32) });
+            .write(|w| unsafe { w.bits((payload.len() + checksum_len) as u32) });
 
         // self.tx_channel
         //     .set_source_end_address(payload.as_ptr() as u32);
@@ -879,18 +1752,103 @@ impl<'p> RadioDriver<'p, RadioOn> {
         Ok(())
     }
 
+    /// Prepare the radio with a data frame, assembling the MAC header from `config`
+    ///
+    /// The destination PAN ID is `config.dst_pan_id`. The source address is the short address
+    /// of `config`, or the extended address when the short address is 0xFFFE (no short
+    /// address). The source PAN ID is `config.src_pan_id`, and is left out when it is the
+    /// same as the destination PAN ID. The header uses the 2006 frame version.
+    ///
+    /// Returns [`RadioError::InvalidConfig`] when `config` is not valid or when an ACK is
+    /// requested for a frame to the broadcast address
+    pub fn prepare_data_frame(
+        &mut self,
+        config: &RadioConfig,
+        seq: u8,
+        dest: DestinationAddress,
+        ack_request: bool,
+        payload: &[u8],
+    ) -> Result<(), RadioError> {
+        config.validate()?;
+
+        if ack_request && dest == DestinationAddress::Short(BROADCAST_ADDR) {
+            return Err(RadioError::InvalidConfig);
+        }
+
+        let pan_id_compression = config.src_pan_id == config.dst_pan_id;
+        let src_extended = config.short_addr == NO_SHORT_ADDR;
+
+        // Data frame, 2006 frame version
+        let mut fcf: u16 = 0b001 | (0b01 << 12);
+        if ack_request {
+            fcf |= 1 << 5;
+        }
+        if pan_id_compression {
+            fcf |= 1 << 6;
+        }
+        fcf |= match dest {
+            DestinationAddress::Short(_) => 0b10,
+            DestinationAddress::Extended(_) => 0b11,
+        } << 10;
+        fcf |= if src_extended { 0b11 } else { 0b10 } << 14;
+
+        let mut frame = [0u8; MAX_PAYLOAD_LEN];
+        let mut len = 0;
+        let mut push = |bytes: &[u8]| -> Result<(), RadioError> {
+            frame
+                .get_mut(len..len + bytes.len())
+                .ok_or(RadioError::PayloadTooBig)?
+                .copy_from_slice(bytes);
+            len += bytes.len();
+            Ok(())
+        };
+
+        push(&fcf.to_le_bytes())?;
+        push(&[seq])?;
+        push(&(config.dst_pan_id as u16).to_le_bytes())?;
+        match dest {
+            DestinationAddress::Short(addr) => push(&addr.to_le_bytes())?,
+            DestinationAddress::Extended(mut addr) => {
+                // Addresses are sent with the least significant byte first
+                addr.reverse();
+                push(&addr)?
+            }
+        }
+        if !pan_id_compression {
+            push(&(config.src_pan_id as u16).to_le_bytes())?;
+        }
+        if src_extended {
+            let mut addr = config.ext_addr;
+            addr.reverse();
+            push(&addr)?;
+        } else {
+            push(&config.short_addr.to_le_bytes())?;
+        }
+        push(payload)?;
+
+        self.prepare(&frame[..len])
+    }
+
     /// Send the packet that has previously been prepared
     #[inline]
     pub fn transmit(&mut self) -> Result<(), RadioError> {
         // We check if we received something and if the channel is clear to send.
-        if !self.is_channel_clear() || self.receiving_packet() {
+        // The RSSI never becomes valid when the radio is not receiving.
+        let clear = self
+            .try_is_channel_clear(CCA_RSSI_VALID_RETRIES)
+            .ok_or(RadioError::UnableToStartTx)?;
+        if !clear || self.receiving_packet() {
             return Err(RadioError::Collision);
         }
 
+        // Such that TX done refers to this transmission.
+        self.clear_event(Event::TxDone);
+
         // Enable TX
         // IMPORTANT: only enable after checking if the channel is clear or if we received a
         // packet. Otherwise TX wont be able to start.
         self.send_csp_op_code(CspOpCode::IsTXOn);
+        self.tx_start = Some(DWT::cycle_count());
 
         let mut counter = 0;
         while Self::xreg_regs().fsmstat1().read().tx_active().bit_is_set() && counter < 3 {
@@ -905,6 +1863,7 @@ impl<'p> RadioDriver<'p, RadioOn> {
             .bit_is_clear()
         {
             // TX was not able to start
+            self.tx_start = None;
             self.send_csp_op_code(CspOpCode::IsFlushTX);
             return Err(RadioError::UnableToStartTx);
         }
@@ -912,13 +1871,192 @@ impl<'p> RadioDriver<'p, RadioOn> {
         Ok(())
     }
 
+    /// Send `payload` when the MAC timer reaches `mac_time`, if the channel is clear
+    ///
+    /// The frame is prepared and a CSP program is loaded that waits for the first compare of
+    /// the MAC timer and strobes [`CspOpCode::STXOnCca`]. After the TX turnaround (12 symbol
+    /// periods, 192 µs), the preamble starts. When the channel is busy at `mac_time`, nothing is
+    /// sent and [`Event::TxDone`] is not raised. The radio must have been in RX for 8 symbol
+    /// periods at `mac_time`, such that the RSSI is valid for the CCA. This uses the CSP
+    /// program memory, the first compare of the MAC timer and `MT_EVENT1`
+    ///
+    /// `mac_time` is in the format of [`RadioDriver::mac_timer_now`], and the MAC timer must be
+    /// running (see [`RadioDriver::start_mac_timer`]). After the frame is prepared, `mac_time`
+    /// must still be at least [`TX_AT_MIN_LEAD_TICKS`] ticks away, otherwise nothing is sent
+    /// and [`RadioError::TooLate`] is returned. Writing a full frame to the TX FIFO takes
+    /// about 20 µs more. The compare only matches the 16-bit timer value, so when `mac_time` is
+    /// more than one timer period (2.048 ms) away, this blocks until it is within one period.
+    /// Returns [`RadioError::MacTimerStopped`] when the MAC timer is not running
+    pub fn transmit_at(&mut self, mac_time: u32, payload: &[u8]) -> Result<(), RadioError> {
+        if !Self::is_mac_timer_running() {
+            return Err(RadioError::MacTimerStopped);
+        }
+
+        while mac_time.wrapping_sub(self.mac_timer_now()) as i32 >= MAC_TIMER_PERIOD_TICKS as i32 {}
+
+        self.prepare(payload)?;
+
+        self.send_csp_op_code(CspOpCode::IsStop);
+        self.send_csp_op_code(CspOpCode::IsClear);
+
+        self.set_mac_timer_compare(MacTimerCompare::Compare1, mac_time as u16);
+        self.set_mac_timer_event1(MacTimerEvent::Compare1);
+
+        // Instructions that are not immediate are appended to the program memory
+        self.send_csp_op_code(CspOpCode::WEvent1);
+        self.send_csp_op_code(CspOpCode::STXOnCca);
+
+        // Such that TX done refers to this transmission.
+        self.clear_event(Event::TxDone);
+        self.tx_start = None;
+
+        // An interrupt between checking the lead time and starting the program could make the
+        // compare pass unnoticed.
+        cortex_m::interrupt::free(|_| {
+            if (mac_time.wrapping_sub(self.mac_timer_now()) as i32) < TX_AT_MIN_LEAD_TICKS as i32 {
+                self.send_csp_op_code(CspOpCode::IsClear);
+                self.send_csp_op_code(CspOpCode::IsFlushTX);
+                return Err(RadioError::TooLate);
+            }
+
+            self.send_csp_op_code(CspOpCode::IsStart);
+
+            Ok(())
+        })
+    }
+
+    /// Send the packet that has previously been prepared, using unslotted CSMA-CA
+    ///
+    /// Before every attempt, the radio waits a random number of unit backoff periods (320 µs)
+    /// between 0 and 2^BE - 1, where the backoff exponent BE starts at `min_be`. When the
+    /// channel is busy, BE is incremented up to `max_be` and the transmission is tried again,
+    /// at most `max_retries` times. The IEEE 802.15.4 defaults are 4 retries, a `min_be` of 3
+    /// and a `max_be` of 5. With a `min_be` of 0, the first attempt is not delayed.
+    ///
+    /// The backoff is timed with the MAC timer, which must be running (see
+    /// [`RadioDriver::start_mac_timer`]), and the random numbers come from the radio, which must
+    /// be in RX. Returns [`RadioError::FailedTransmission`] when the channel stayed busy, and
+    /// [`RadioError::MacTimerStopped`] when the MAC timer is not running.
+    pub fn transmit_csma(
+        &mut self,
+        max_retries: u8,
+        min_be: u8,
+        max_be: u8,
+    ) -> Result<(), RadioError> {
+        if !Self::is_mac_timer_running() {
+            return Err(RadioError::MacTimerStopped);
+        }
+        assert!(min_be <= max_be && max_be <= 8);
+
+        let mut be = min_be;
+        for _ in 0..=max_retries {
+            let ticks = self.random_bits(be) as u32 * UNIT_BACKOFF_TICKS;
+            let start = self.mac_timer_now();
+            while self.mac_timer_now().wrapping_sub(start) < ticks {}
+
+            match self.transmit() {
+                Err(RadioError::Collision) => be = (be + 1).min(max_be),
+                result => return result,
+            }
+        }
+
+        Err(RadioError::FailedTransmission)
+    }
+
     /// Prepare and transmit a packet
+    ///
+    /// Returns the errors of [`RadioDriver::prepare`] for an empty or too big payload, in which
+    /// case nothing is sent
     #[inline]
     pub fn send(&mut self, payload: &[u8]) -> Result<(), RadioError> {
-        self.prepare(payload).expect("unable to prepare");
+        self.prepare(payload)?;
         self.transmit()
     }
 
+    /// Transmit a frame and wait for its ACK
+    ///
+    /// `payload` is the frame without checksum, starting with the frame control field. The ACK
+    /// request bit is set before the frame is sent. After the transmission is done, received
+    /// frames are read until an ACK with the same sequence number arrives or `timeout` expires.
+    /// The timeout is measured with the MAC timer, which must be running (see
+    /// [`RadioDriver::start_mac_timer`]).
+    ///
+    /// The ACK is recognized by its frame type and sequence number, not by [`Event::TxAckDone`],
+    /// which is raised when this radio sends an ACK itself (auto ACK). Other frames received
+    /// while waiting are dropped.
+    ///
+    /// Returns whether the ACK was received, or [`RadioError::MacTimerStopped`] when the MAC timer
+    /// is not running. Retransmitting the frame is left to the caller.
+    pub fn send_with_ack(&mut self, payload: &[u8], timeout: Duration) -> Result<bool, RadioError> {
+        if !Self::is_mac_timer_running() {
+            return Err(RadioError::MacTimerStopped);
+        }
+
+        // Frame control field and sequence number
+        if payload.len() < 3 {
+            return Err(RadioError::IncorrectFrame);
+        }
+
+        if payload.len() > MAX_PAYLOAD_LEN {
+            return Err(RadioError::PayloadTooBig);
+        }
+
+        let mut frame = [0u8; MAX_PAYLOAD_LEN];
+        frame[..payload.len()].copy_from_slice(payload);
+        // ACK request
+        frame[0] |= 1 << 5;
+        let seq = frame[2];
+
+        self.prepare(&frame[..payload.len()])?;
+        self.transmit()?;
+
+        while self.sending() {}
+
+        let ticks = (timeout.as_micros() * 32).min(u32::MAX as u128) as u32;
+        let start = self.mac_timer_now();
+
+        while self.mac_timer_now().wrapping_sub(start) < ticks {
+            if !self.received_packet() {
+                continue;
+            }
+
+            let mut buffer = [0u8; MAX_PACKET_LEN];
+            let len = self.read(&mut buffer) as usize;
+
+            // An ACK frame only has a frame control field and a sequence number.
+            if len == 3 && self.last_crc_ok && buffer[0] & 0b111 == 0b010 && buffer[2] == seq {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Return the time between the start of the last transmission and the TX done event
+    ///
+    /// This waits until the frame is sent. The time is measured with the DWT cycle counter, so
+    /// `timer` must be created before transmitting (see [`MonoTimer::new`]), and the
+    /// transmission can not take longer than a full wrap of the counter (134 s at 32 MHz).
+    /// It includes the preamble, the SFD and the checksum, which makes it usable to
+    /// characterize the on-air time per frame size. Returns `None` when no transmission was
+    /// started since the last call, or when the TX done event is not raised within 10 ms of the
+    /// start of the transmission
+    pub fn time_last_tx(&mut self, timer: MonoTimer) -> Option<Duration> {
+        let start = self.tx_start.take()?;
+        let limit = (timer.frequency() as u64 * TX_DONE_TIMEOUT_US / 1_000_000) as u32;
+
+        while !self.is_interrupt_pending(Event::TxDone) {
+            if DWT::cycle_count().wrapping_sub(start) > limit {
+                return None;
+            }
+        }
+        let cycles = DWT::cycle_count().wrapping_sub(start);
+
+        Some(Duration::from_nanos(
+            cycles as u64 * 1_000_000_000 / timer.frequency() as u64,
+        ))
+    }
+
     /// Return the status of TX
     #[inline]
     pub fn sending(&self) -> bool {
@@ -926,78 +2064,106 @@ impl<'p> RadioDriver<'p, RadioOn> {
     }
 
     /// Read a received packet into a buffer
+    ///
+    /// Returns the length of the packet without the checksum, or 0 when the length byte is not
+    /// valid or the packet does not fit in `buffer`. In that case, the RX FIFO is flushed since
+    /// the start of the next frame can not be found anymore. When bad frames are rejected (see
+    /// [`RadioDriver::reject_bad_frames`]), 0 is also returned for a frame with an incorrect CRC.
+    ///
+    /// With auto CRC disabled, the received checksum is not checked but returned as the last
+    /// two bytes of the packet, and [`RadioDriver::last_frame_crc_ok`] returns `false`.
+    ///
+    /// After reading a packet, the RX FIFO is left untouched when it still holds (part of) the
+    /// next frame. It is only flushed when FIFOP is set while the FIFO is empty, which
+    /// indicates an RX overflow.
     #[inline]
     pub fn read(&mut self, buffer: &mut [u8]) -> u32 {
+        self.last_crc_ok = false;
+
         let len: u32 = Self::sfr_regs().rfdata().read().bits();
 
-        if len > 127 {
+        if len > MAX_PACKET_LEN as u32 {
             // If bigger than max packet len
             // bad sync error
-
-            self.send_csp_op_code(CspOpCode::IsFlushRx);
+            self.flush_rx();
             return 0;
         }
 
         if len <= 4 {
             // If smaller than min packet len
-
-            self.send_csp_op_code(CspOpCode::IsFlushRx);
+            self.flush_rx();
             return 0;
         }
 
-        if len - 2 > buffer.len() as u32 {
-            // Remove checksum length
-            // message too long
+        // Without auto CRC, the checksum is received as is and returned with the payload.
+        let autocrc = self.is_autocrc_enabled();
+        let payload_len = if autocrc {
+            len - CHECKSUM_LEN as u32
+        } else {
+            len
+        };
 
-            self.send_csp_op_code(CspOpCode::IsFlushRx);
+        if payload_len > buffer.len() as u32 {
+            // message too long
+            self.flush_rx();
             return 0;
         }
 
-        //let len = len - 2;
-
-        // Don't use DMA for short messages
-        //if len > 5 {
-        //self.rx_channel
-        //.set_destination_end_address(buffer.as_ptr() as u32 + len - 1);
-        //self.rx_channel.use_burst(true);
-        //self.rx_channel
-        //.set_transfer_mode(dma::TransferMode::AutoRequest);
-        //self.rx_channel.set_transfer_size(len as u8 - 1);
+        for b in buffer[..payload_len as usize].iter_mut() {
+            *b = Self::sfr_regs().rfdata().read().bits() as u8;
+        }
 
-        //self.rx_channel.enable();
-        //self.rx_channel.request();
+        let payload_len = if autocrc {
+            // The checksum is replaced by the RSSI and CRC/Corr bytes.
+            // They are read such that the next frame starts at the head of the FIFO.
+            let _rssi = Self::sfr_regs().rfdata().read().bits();
+            let crc_corr = Self::sfr_regs().rfdata().read().bits();
+            self.last_crc_ok = crc_corr & 0x80 != 0;
 
-        //while self.rx_channel.get_mode() != dma::TransferMode::Stop {}
-        //} else {
-        for i in 0..len {
-            buffer[i as usize] = Self::sfr_regs().rfdata().read().bits() as u8;
-        }
-        //}
+            if self.reject_bad_frames && !self.last_crc_ok {
+                0
+            } else {
+                payload_len
+            }
+        } else {
+            payload_len
+        };
 
         if Self::xreg_regs().fsmstat1().read().fifop().bit_is_set() {
             if Self::xreg_regs().fsmstat1().read().fifo().bit_is_set() {
+                // Another frame is ready to be read.
                 cortex_m::asm::sev();
             } else {
-                self.send_csp_op_code(CspOpCode::IsFlushRx);
+                // RX overflow
+                self.flush_rx();
             }
         }
 
-        // let rssi = buffer[len as usize - 2] - 73;
-        // let crc_corr = buffer[len as usize - 1];
+        payload_len
+    }
 
-        // if ((crc_corr & 0x80) >> 7) & 0b1 == 1 {
-        // packetbuf_set_attr(rssi, rssi);
-        // packetbuf_set_attr(link_quality, crc_corr & 0x7f)
-        // }
+    /// Check if the CRC of the last frame read with [`RadioDriver::read`] was correct
+    ///
+    /// Frames with an incorrect CRC are still returned by `read`, unless they are rejected
+    /// with [`RadioDriver::reject_bad_frames`]. Use this to drop or count them
+    #[inline]
+    pub fn last_frame_crc_ok(&self) -> bool {
+        self.last_crc_ok
+    }
 
-        // read the RSSI and CRC/Corr bytes
-        // let rssi = self.sfr.rfdata.read().bits() - 73;
-        // let crc_corr = self.sfr.rfdata.read().bits();
+    /// Read a received packet into a [`Frame`]
+    ///
+    /// Returns `None` when no valid frame was read.
+    #[inline]
+    pub fn read_frame(&mut self) -> Option<Frame> {
+        let mut buffer = [0u8; MAX_PACKET_LEN];
+        let len = self.read(&mut buffer) as usize;
 
-        // buffer[buffer.len() - 2] = rssi as u8;
-        // buffer[buffer.len() - 1] = crc_corr as u8;
+        if len == 0 {
+            return None;
+        }
 
-        len - 2
+        Frame::new(&buffer[..len])
     }
 
     /// Check if thradio driver is currently receiving a packet
@@ -1016,6 +2182,96 @@ impl<'p> RadioDriver<'p, RadioOn> {
         Self::xreg_regs().fsmstat1().read().fifop().bit()
     }
 
+    /// Check if an SFD was received or sent, and the frame is not yet complete
+    ///
+    /// This is set before the rest of the frame is received, which makes it usable to detect
+    /// the start of a frame in poll mode
+    #[inline]
+    pub fn sfd_active(&self) -> bool {
+        Self::xreg_regs().fsmstat1().read().sfd().bit_is_set()
+    }
+
+    /// Check if the radio FSM is in one of the receive states
+    ///
+    /// This reads the state of the hardware, which can differ from the type state of the
+    /// driver, for example after an RFOff strobe. The radio is not in RX while it is
+    /// transmitting or in idle
+    #[inline]
+    pub fn is_rx_on(&self) -> bool {
+        let xreg = Self::xreg_regs();
+
+        xreg.fsmstat0().read().fsm_ffctrl_state().bits() != 0
+            && xreg.fsmstat1().read().rx_active().bit_is_set()
+    }
+
+    /// Check if the length byte (PHR) of the frame that is being received is in the RX FIFO
+    ///
+    /// When this returns `true`, the first byte of the RX FIFO is the length of the frame
+    #[inline]
+    pub fn frame_length_available(&self) -> bool {
+        self.rx_fifo_count() > 0
+    }
+
+    /// Return the number of bytes in the RX FIFO, without consuming them
+    ///
+    /// A complete frame is in the RX FIFO when the count is larger than its length byte (see
+    /// [`RadioDriver::frame_length_available`]), which includes the checksum. Checking this
+    /// before calling [`RadioDriver::read`] in poll mode avoids reading a frame that is not
+    /// completely received yet
+    #[inline]
+    pub fn rx_fifo_count(&self) -> u8 {
+        Self::xreg_regs().rxfifocnt().read().rxfifocnt().bits()
+    }
+
+    /// Write a byte to the TX FIFO
+    ///
+    /// A frame in the TX FIFO starts with its length byte, which includes the 2-byte checksum
+    /// when auto CRC is enabled, followed by the frame itself. Only the length byte and the
+    /// payload are written, the radio appends the checksum. [`RadioDriver::prepare`] flushes the
+    /// TX FIFO and writes a complete frame, this allows writing a frame incrementally instead.
+    /// Returns [`RadioError::PayloadTooBig`] when the TX FIFO is full
+    #[inline]
+    pub fn push_tx_byte(&mut self, byte: u8) -> Result<(), RadioError> {
+        if Self::xreg_regs().txfifocnt().read().bits() as usize >= TX_FIFO_LEN {
+            return Err(RadioError::PayloadTooBig);
+        }
+
+        Self::sfr_regs()
+            .rfdata()
+            .write(|w| unsafe { w.bits(byte as u32) });
+        Ok(())
+    }
+
+    /// Remove the first byte from the RX FIFO
+    ///
+    /// Reading advances the RX FIFO: the first byte of a frame is its length byte, followed by
+    /// the frame and the 2 bytes that replace the checksum when auto CRC is enabled (the RSSI
+    /// and the CRC OK bit with the correlation value). Once a byte of a frame is popped,
+    /// [`RadioDriver::read`] can not be used for that frame anymore, so the remaining bytes must
+    /// be popped as well. Returns `None` when the RX FIFO is empty
+    #[inline]
+    pub fn pop_rx_byte(&mut self) -> Option<u8> {
+        if self.rx_fifo_count() == 0 {
+            return None;
+        }
+
+        Some(Self::sfr_regs().rfdata().read().bits() as u8)
+    }
+
+    /// Return the byte at `index` of the RX FIFO, without removing it
+    ///
+    /// Index 0 is the first byte in the RX FIFO, which is the length byte of the first frame
+    /// when no bytes were popped. This allows parsing the MAC header of a frame while it is
+    /// still being received. Returns `None` when the byte is not in the RX FIFO (yet)
+    #[inline]
+    pub fn peek_rx(&self, index: usize) -> Option<u8> {
+        if index >= self.rx_fifo_count() as usize {
+            return None;
+        }
+
+        Some(Self::peek_rx_fifo(index))
+    }
+
     #[inline]
     pub fn is_rssi_valid(&self) -> bool {
         Self::xreg_regs()
@@ -1025,6 +2281,158 @@ impl<'p> RadioDriver<'p, RadioOn> {
             .bit_is_set()
     }
 
+    /// Returns the mean RSSI value in dB of `samples` readings
+    ///
+    /// The readings are spaced by 128 µs (eight symbol periods), which is the rate at which the
+    /// radio updates the RSSI. Readings taken while the RSSI is not valid, or that read the
+    /// invalid value 0x80, are skipped instead of waited for. Returns `None` when no valid
+    /// reading was taken
+    pub fn average_rssi(&mut self, samples: u8, delay: &mut impl DelayNs) -> Option<i32> {
+        let mut sum = 0i32;
+        let mut count = 0i32;
+
+        for i in 0..samples {
+            if i != 0 {
+                delay.delay_us(128);
+            }
+
+            if !self.is_rssi_valid() {
+                continue;
+            }
+
+            let rssi = Self::xreg_regs().rssi().read().rssi_val().bits();
+            if rssi == 0x80 {
+                continue;
+            }
+
+            // The RSSI register is a signed value.
+            sum += rssi as i8 as i32;
+            count += 1;
+        }
+
+        if count == 0 {
+            None
+        } else {
+            Some(sum / count - 73)
+        }
+    }
+
+    /// Peek at a byte of the RX FIFO, relative to the first byte, without removing it
+    fn peek_rx_fifo(index: usize) -> u8 {
+        let first = Self::xreg_regs().rxfirst_ptr().read().rxfirst_ptr().bits() as usize;
+        let addr = RX_FIFO_RAM_ADDR + (((first + index) % RX_FIFO_LEN) * 4) as u32;
+        unsafe { core::ptr::read_volatile(addr as *const u32) as u8 }
+    }
+
+    /// Return the source address of the frame that is being received
+    ///
+    /// The address fields are read from the RX FIFO without removing them, assuming that the
+    /// frame being received is the only frame in the RX FIFO. Returns `None` when the frame has
+    /// no source address
+    fn peek_source_address() -> Option<SourceAddress> {
+        let fcf = Self::peek_rx_fifo(1) as u16 | (Self::peek_rx_fifo(2) as u16) << 8;
+        let pan_id_compression = fcf & (1 << 6) != 0;
+        let dest_mode = (fcf >> 10) & 0b11;
+        let src_mode = (fcf >> 14) & 0b11;
+
+        // Length, frame control field and sequence number
+        let mut offset = 4;
+        match dest_mode {
+            0b10 => offset += 2 + 2,
+            0b11 => offset += 2 + 8,
+            _ => (),
+        }
+        if src_mode != 0 && !(pan_id_compression && dest_mode != 0) {
+            offset += 2;
+        }
+
+        match src_mode {
+            0b10 => Some(SourceAddress::Short(
+                Self::peek_rx_fifo(offset) as u16 | (Self::peek_rx_fifo(offset + 1) as u16) << 8,
+            )),
+            0b11 => {
+                let mut addr = [0u8; 8];
+                for (i, b) in addr.iter_mut().enumerate() {
+                    *b = Self::peek_rx_fifo(offset + i);
+                }
+                Some(SourceAddress::Extended(addr))
+            }
+            _ => None,
+        }
+    }
+
+    /// Decide the frame pending bit of the ACK with a lookup of the source address
+    ///
+    /// This is an alternative to the source address matching table of the radio, for when it
+    /// is too small. Call this from the radio interrupt handler on [`Event::FrameAccepted`] (or
+    /// [`Event::SrcMatchFound`]). When the received frame requests an ACK and has a source
+    /// address, `lookup` is called with that address: the ACK is then sent with the frame
+    /// pending bit set when it returns `true` (`SAckPend`), and without otherwise (`SAck`).
+    /// Returns the result of `lookup`, or `None` when it was not called.
+    ///
+    /// The strobe must be issued before the end of the frame, since the ACK is sent 12 symbol
+    /// periods (192 µs) after the frame is received. For a short frame like a data request
+    /// this leaves only a few hundred microseconds after the address fields are received, so
+    /// the interrupt must have a high priority and `lookup` must be fast.
+    pub fn handle_frame_pending(
+        &mut self,
+        lookup: impl FnOnce(SourceAddress) -> bool,
+    ) -> Option<bool> {
+        let ack_request = Self::peek_rx_fifo(1) & (1 << 5) != 0;
+        if !ack_request {
+            return None;
+        }
+
+        let pending = lookup(Self::peek_source_address()?);
+
+        self.send_csp_op_code(if pending {
+            CspOpCode::SAckPend
+        } else {
+            CspOpCode::SAck
+        });
+
+        Some(pending)
+    }
+
+    /// Abort the auto ACK of the frame that is being received when it is a broadcast frame
+    ///
+    /// Call this from the radio interrupt handler on [`Event::FrameAccepted`], when broadcast
+    /// ACKs are suppressed (see [`RadioDriver::suppress_broadcast_ack`]). When the frame
+    /// requests an ACK and is sent to the short broadcast address, the ACK is aborted with
+    /// [`CspOpCode::IsNack`]. Like for [`RadioDriver::handle_frame_pending`], this must happen
+    /// before the end of the frame. Returns whether the ACK was aborted
+    pub fn handle_broadcast_ack(&mut self) -> bool {
+        if !self.suppress_broadcast_ack {
+            return false;
+        }
+
+        let ack_request = Self::peek_rx_fifo(1) & (1 << 5) != 0;
+        let dest_mode = (Self::peek_rx_fifo(2) >> 2) & 0b11;
+        if !ack_request || dest_mode != 0b10 {
+            return false;
+        }
+
+        // Length, frame control field, sequence number and destination PAN ID
+        let dest = Self::peek_rx_fifo(6) as u16 | (Self::peek_rx_fifo(7) as u16) << 8;
+        if dest != BROADCAST_ADDR {
+            return false;
+        }
+
+        self.send_csp_op_code(CspOpCode::IsNack);
+        true
+    }
+
+    /// Return the sequence number of the first frame in the RX FIFO, without removing it
+    ///
+    /// This is the frame that was received last when frames are read as they arrive, and the
+    /// sequence number that auto ACK puts in its ACK. Returns `None` when the sequence number is
+    /// not in the RX FIFO (yet), e.g. after the frame was read with [`RadioDriver::read`]
+    #[inline]
+    pub fn last_rx_seqno(&self) -> Option<u8> {
+        // Length and frame control field
+        self.peek_rx(3)
+    }
+
     /// Perform a clear channel assesment to find out if there is a packet in the air
     #[inline]
     pub fn is_channel_clear(&self) -> bool {
@@ -1034,6 +2442,23 @@ impl<'p> RadioDriver<'p, RadioOn> {
         Self::xreg_regs().fsmstat1().read().cca().bit_is_set()
     }
 
+    /// Perform a clear channel assesment, polling the RSSI valid flag at most `retries` times
+    ///
+    /// Returns `None` when the RSSI did not become valid, which happens when the radio is not
+    /// in RX (e.g. when the FSM is in the wrong state). [`Self::is_channel_clear`] waits forever
+    /// in that case.
+    pub fn try_is_channel_clear(&self, retries: u32) -> Option<bool> {
+        let mut remaining = retries;
+        while !self.is_rssi_valid() {
+            if remaining == 0 {
+                return None;
+            }
+            remaining -= 1;
+        }
+
+        Some(Self::xreg_regs().fsmstat1().read().cca().bit_is_set())
+    }
+
     /// Return random data.
     ///
     /// **NOTE**: Use this function to seed the Random Number Generator
@@ -1041,4 +2466,36 @@ impl<'p> RadioDriver<'p, RadioOn> {
     pub fn random_data(&self) -> u8 {
         Self::xreg_regs().rfrnd().read().irnd().bit() as u8
     }
+
+    /// Return `n` random bits, gathered from the noise of the receiver
+    ///
+    /// Every read of the RFRND register gives two bits, one from the I channel and one from the
+    /// Q channel of the receiver ADC. Consecutive reads are spaced by 1 µs, such that they come
+    /// from different ADC samples. The radio must be in RX (preferably with
+    /// [`RxMode::InfiniteReception`]) and the RSSI must be valid, otherwise the bits are not
+    /// random.
+    ///
+    /// The RNG of the SoC ADC takes a 16-bit seed, so 16 bits are needed to seed it, see
+    /// [`crate::rng::RngDriver::new_with_radio_seed`]. `n` must not be larger than 16.
+    pub fn random_bits(&self, n: u8) -> u16 {
+        assert!(n <= 16);
+
+        let mut bits: u16 = 0;
+        let mut i = 0;
+        while i < n {
+            let rnd = Self::xreg_regs().rfrnd().read();
+
+            bits = (bits << 1) | rnd.irnd().bit() as u16;
+            i += 1;
+
+            if i < n {
+                bits = (bits << 1) | rnd.qrnd().bit() as u16;
+                i += 1;
+            }
+
+            cortex_m::asm::delay(RANDOM_BITS_DELAY_CYCLES);
+        }
+
+        bits
+    }
 }
@@ -4,7 +4,7 @@ use core::marker::PhantomData;
 
 use cc2538_pac::{soc_adc, SocAdc};
 
-use crate::radio::{Radio, RadioDriver, RadioOff, RadioOn, RxMode};
+use crate::radio::{Radio, RadioDriver, RadioError, RadioOff, RadioOn, RxMode};
 
 pub struct NotSeeded;
 pub struct Seeded;
@@ -83,7 +83,20 @@ impl<'p> RngDriver<'p, NotSeeded> {
         }
     }
 
-    pub fn new_with_radio_seed(_rng: &'p mut SocAdc, radio: &mut Radio) -> RngDriver<'p, Seeded> {
+    /// Seed the RNG with random bits of the radio.
+    ///
+    /// Returns [`RadioError::NotClocked`] when the radio is off and the clock of the RF core is
+    /// not enabled. The radio is left untouched in that case.
+    pub fn new_with_radio_seed(
+        _rng: &'p mut SocAdc,
+        radio: &mut Radio,
+    ) -> Result<RngDriver<'p, Seeded>, RadioError> {
+        if let Radio::Off(r) = radio {
+            if !r.is_clocked() {
+                return Err(RadioError::NotClocked);
+            }
+        }
+
         // Make sure the RNG is on.
         let this = Self {
             _rng: PhantomData,
@@ -97,7 +110,7 @@ impl<'p> RngDriver<'p, NotSeeded> {
         core::mem::swap(&mut r, radio);
 
         let (mut r, enabled) = match r {
-            Radio::Off(r) => (r.enable(None), false),
+            Radio::Off(r) => (r.enable(None)?, false),
             Radio::On(r) => (r, true),
             Radio::Undefined => unreachable!(),
         };
@@ -109,11 +122,9 @@ impl<'p> RngDriver<'p, NotSeeded> {
 
         let mut seed: u16 = 0;
 
+        // 0x0000 and 0x8003 are not valid seeds of the LFSR.
         while seed == 0x0000 || seed == 0x8003 {
-            for _ in 0..16 {
-                seed |= r.random_data() as u16;
-                seed <<= 1;
-            }
+            seed = r.random_bits(16);
         }
 
         // Writing twice to NRDL will seed the RNG.
@@ -138,9 +149,9 @@ impl<'p> RngDriver<'p, NotSeeded> {
         // Move back the radio.
         core::mem::swap(&mut r, radio);
 
-        RngDriver {
+        Ok(RngDriver {
             _rng: PhantomData,
             _state: PhantomData,
-        }
+        })
     }
 }
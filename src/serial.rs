@@ -7,13 +7,21 @@ use core::marker::PhantomData;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 
+use crate::pac::Interrupt as interrupt;
+use cortex_m::peripheral::NVIC;
+
 use crate::gpio::{AltFunc, PXx};
+use crate::interrupt::{InterruptExt, WakerCell};
 use crate::sys_ctrl::ClockConfig;
 use crate::time::*;
 
+use paste::paste;
+
 use embedded_io::ErrorType;
 use embedded_io::Read as SerialRead;
 use embedded_io::Write as SerialWrite;
+use embedded_io_async::Read as AsyncSerialRead;
+use embedded_io_async::Write as AsyncSerialWrite;
 
 pub trait TxPin<UART> {}
 pub trait RxPin<UART> {}
@@ -29,6 +37,18 @@ use core::fmt::Write;
 pub enum Event {
     Rxne,
     Txe,
+    /// Data is in the RX FIFO and was not read for 32 bit periods.
+    ReceiveTimeout,
+}
+
+/// The fill level of a FIFO at which an interrupt is triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    Half = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
 }
 
 #[derive(Debug)]
@@ -39,6 +59,25 @@ pub enum Error {
     Parity,
 }
 
+/// Compute the baud rate divisors `(IBRD, FBRD, HSE)` for an IO clock of `clk` Hz.
+///
+/// High speed mode (HSE, 8 samples per bit instead of 16) is used when `baud * 16` exceeds the
+/// clock. Being a `const fn`, the divisors of a fixed baud rate can be computed at compile
+/// time and passed to e.g. [`Serial::uart0_with_divisors`]:
+///
+/// ```ignore
+/// const DIVISORS: (u16, u8, bool) = baud_divisors(16_000_000, 115_200);
+/// ```
+pub const fn baud_divisors(clk: u32, baud: u32) -> (u16, u8, bool) {
+    let hse = baud * 16 > clk;
+    let b_rate = if hse { baud / 2 } else { baud };
+
+    // The divisor in 1/64ths, rounded to the nearest value.
+    let div = ((clk * 8) / b_rate).div_ceil(2);
+
+    ((div / 64) as u16, (div % 64) as u8, hse)
+}
+
 pub struct Rx<UART> {
     _uart: PhantomData<UART>,
 }
@@ -65,25 +104,40 @@ macro_rules! uart {
                     TX: TxPin<$UARTX>,
                     RX: RxPin<$UARTX>,
                 {
-                    let clk = clocks.io_freq();
-                    let mut b_rate = baud_rate;
+                    let divisors = baud_divisors(clocks.io_freq(), baud_rate);
+                    paste! { Self::[<$uartX _with_divisors>](uart, pins, divisors) }
+                }
 
-                    uart.cc().modify(|_,w| unsafe { w.cs().bits(0x1) });
+                paste! {
+                /// Configures a UART peripheral with baud rate divisors computed by
+                /// [`baud_divisors`], e.g. at compile time.
+                pub fn [<$uartX _with_divisors>](
+                    uart: $UARTX,
+                    pins: (TX, RX),
+                    (ibrd, fbrd, hse): (u16, u8, bool),
+                ) -> Self
+                where
+                    TX: TxPin<$UARTX>,
+                    RX: RxPin<$UARTX>,
+                {
+                    debug_assert!(
+                        crate::sys_ctrl::[<is_ $uartX _clock_enabled>](),
+                        concat!(
+                            stringify!($UARTX),
+                            " clock not enabled, call `enable_",
+                            stringify!($uartX),
+                            "_in_active_mode` first"
+                        )
+                    );
 
-                    if baud_rate*16 > clk {
-                        // Enable high speed mode.
-                        uart.ctl().modify(|_,w| w.hse().set_bit());
-                        b_rate /= 2;
-                    } else {
-                        // Disable high speed mode
-                        uart.ctl().modify(|_, w| w.hse().clear_bit());
-                    }
+                    uart.cc().modify(|_,w| unsafe { w.cs().bits(0x1) });
 
-                    let div = (((clk * 8)/b_rate)+1)/2;
+                    // Enable or disable high speed mode.
+                    uart.ctl().modify(|_, w| w.hse().bit(hse));
 
                     // Set the baud rate
-                    uart.ibrd().modify(|_, w| unsafe { w.divint().bits((div/64) as u16) });
-                    uart.fbrd().modify(|_, w| unsafe { w.divfrac().bits((div%64) as u8) });
+                    uart.ibrd().modify(|_, w| unsafe { w.divint().bits(ibrd) });
+                    uart.fbrd().modify(|_, w| unsafe { w.divfrac().bits(fbrd) });
 
                     // Set parity, data length and number of stop bits
                     uart.lcrh().modify(|_, w| unsafe { w.wlen().bits(0x3).pen().clear_bit() });
@@ -98,21 +152,37 @@ macro_rules! uart {
                         pins,
                     }
                 }
+                }
+
+                /// Set the FIFO levels at which the RX and TX interrupts are triggered.
+                ///
+                /// The RX interrupt is triggered when the RX FIFO fills up to `rx` or more, the TX
+                /// interrupt when the TX FIFO drains to `tx` or less. Both are at
+                /// [`FifoLevel::Half`] after reset.
+                pub fn set_fifo_thresholds(&mut self, rx: FifoLevel, tx: FifoLevel) {
+                    self.uart.ifls().write(|w| unsafe {
+                        w.rxiflsel().bits(rx as u8).txiflsel().bits(tx as u8)
+                    });
+                }
 
                 /// Start listening for an interrupt event.
+                ///
+                /// The UART interrupt is unmasked in the NVIC as well. The event is handled by
+                /// the interrupt handler of the application, which also has to call the
+                /// interrupt hook of the UART for the async `Read` and `Write` implementations.
                 pub fn listen(&mut self, event: Event) {
-                    match event {
-                        Event::Rxne => self.uart.im().modify(|_, w| w.rxim().set_bit()),
-                        Event::Txe => self.uart.im().modify(|_, w| w.txim().set_bit()),
-                    };
+                    paste! { [<listen_ $uartX>](event) };
+                    self.unmask_interrupt();
                 }
 
                 /// Stop listening for an interrupt event.
+                ///
+                /// The UART interrupt is masked in the NVIC when no event is listened to anymore.
                 pub fn unlisten(&mut self, event: Event) {
-                    match event {
-                        Event::Rxne => self.uart.im().modify(|_, w| w.rxim().clear_bit()),
-                        Event::Txe => self.uart.im().modify(|_, w| w.txim().clear_bit()),
-                    };
+                    paste! { [<unlisten_ $uartX>](event) };
+                    if self.uart.im().read().bits() == 0 {
+                        self.mask_interrupt();
+                    }
                 }
 
                 /// Splits the `Serial` abstraction into a transmitter and a receiver half.
@@ -133,6 +203,170 @@ macro_rules! uart {
                 }
             }
 
+            paste! {
+                impl<TX, RX> InterruptExt for Serial<$UARTX, (TX, RX)> {
+                    const INTERRUPT: interrupt = interrupt::[<$UARTX:upper>];
+                }
+
+                /// Waker of the task waiting for data to be received.
+                static [<$UARTX:upper _RX_WAKER>]: WakerCell = WakerCell::new();
+                /// Waker of the task waiting for space in the TX FIFO or for the transmitter to go
+                /// idle.
+                static [<$UARTX:upper _TX_WAKER>]: WakerCell = WakerCell::new();
+
+                fn [<listen_ $uartX>](event: Event) {
+                    let uart = unsafe { &(*$UARTX::ptr()) };
+                    match event {
+                        Event::Rxne => uart.im().modify(|_, w| w.rxim().set_bit()),
+                        Event::Txe => uart.im().modify(|_, w| w.txim().set_bit()),
+                        Event::ReceiveTimeout => uart.im().modify(|_, w| w.rtim().set_bit()),
+                    };
+                }
+
+                fn [<unlisten_ $uartX>](event: Event) {
+                    let uart = unsafe { &(*$UARTX::ptr()) };
+                    match event {
+                        Event::Rxne => uart.im().modify(|_, w| w.rxim().clear_bit()),
+                        Event::Txe => uart.im().modify(|_, w| w.txim().clear_bit()),
+                        Event::ReceiveTimeout => uart.im().modify(|_, w| w.rtim().clear_bit()),
+                    };
+                }
+
+                #[doc = "Handle the `" $UARTX:upper "` interrupt for the async `Read` and `Write`"]
+                #[doc = "implementations of the UART."]
+                ///
+                /// The HAL does not define the interrupt handler, such that the application can
+                /// handle other UART events as well. The events a task is waiting for are
+                /// cleared and no longer listened to, the others are left pending for the
+                /// handler of the application. The handler must call this function:
+                ///
+                /// ```ignore
+                /// #[interrupt]
+                #[doc = "fn " $UARTX:upper "() {"]
+                #[doc = "    serial::on_" $uartX "_interrupt();"]
+                /// }
+                /// ```
+                pub fn [<on_ $uartX _interrupt>]() {
+                    let uart = unsafe { &(*$UARTX::ptr()) };
+                    let mis = uart.mis().read();
+
+                    // Events without a waiting task are left to the handler of the application.
+                    if (mis.rxmis().bit_is_set() || mis.rtmis().bit_is_set())
+                        && [<$UARTX:upper _RX_WAKER>].is_registered()
+                    {
+                        [<unlisten_ $uartX>](Event::Rxne);
+                        [<unlisten_ $uartX>](Event::ReceiveTimeout);
+                        uart.icr().write(|w| w.rxic().set_bit().rtic().set_bit());
+                        [<$UARTX:upper _RX_WAKER>].wake();
+                    }
+
+                    if mis.txmis().bit_is_set() && [<$UARTX:upper _TX_WAKER>].is_registered() {
+                        [<unlisten_ $uartX>](Event::Txe);
+                        uart.icr().write(|w| w.txic().set_bit());
+                        [<$UARTX:upper _TX_WAKER>].wake();
+                    }
+                }
+
+                impl AsyncSerialRead for Rx<$UARTX> {
+                    /// Wait until data is received and read it.
+                    ///
+                    /// The RX and receive timeout interrupts wake up the task, such that data
+                    /// is returned as soon as it is received.
+                    async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+                        if buffer.is_empty() {
+                            return Ok(0);
+                        }
+
+                        let uart = unsafe { &(*$UARTX::ptr()) };
+
+                        core::future::poll_fn(|cx: &mut Context<'_>| {
+                            [<$UARTX:upper _RX_WAKER>].register(cx.waker());
+                            [<listen_ $uartX>](Event::Rxne);
+                            [<listen_ $uartX>](Event::ReceiveTimeout);
+                            unsafe { NVIC::unmask(interrupt::[<$UARTX:upper>]) };
+
+                            let mut n = 0;
+                            while n < buffer.len() && uart.fr().read().rxfe().bit_is_clear() {
+                                buffer[n] = uart.dr().read().data().bits();
+                                n += 1;
+                            }
+
+                            if n == 0 {
+                                return Poll::Pending;
+                            }
+
+                            [<unlisten_ $uartX>](Event::Rxne);
+                            [<unlisten_ $uartX>](Event::ReceiveTimeout);
+                            [<$UARTX:upper _RX_WAKER>].clear();
+
+                            Poll::Ready(Ok(n))
+                        })
+                        .await
+                    }
+                }
+
+                impl AsyncSerialWrite for Tx<$UARTX> {
+                    /// Wait until there is space in the TX FIFO and write as much as fits.
+                    async fn write(&mut self, buffer: &[u8]) -> Result<usize, Self::Error> {
+                        if buffer.is_empty() {
+                            return Ok(0);
+                        }
+
+                        let uart = unsafe { &(*$UARTX::ptr()) };
+
+                        // The TX interrupt is raised based on the FIFO level.
+                        uart.ctl().modify(|_, w| w.eot().clear_bit());
+
+                        core::future::poll_fn(|cx: &mut Context<'_>| {
+                            [<$UARTX:upper _TX_WAKER>].register(cx.waker());
+                            [<listen_ $uartX>](Event::Txe);
+                            unsafe { NVIC::unmask(interrupt::[<$UARTX:upper>]) };
+
+                            let mut n = 0;
+                            while n < buffer.len() && uart.fr().read().txff().bit_is_clear() {
+                                uart.dr().write(|w| unsafe { w.data().bits(buffer[n]) });
+                                n += 1;
+                            }
+
+                            if n == 0 {
+                                return Poll::Pending;
+                            }
+
+                            [<unlisten_ $uartX>](Event::Txe);
+                            [<$UARTX:upper _TX_WAKER>].clear();
+
+                            Poll::Ready(Ok(n))
+                        })
+                        .await
+                    }
+
+                    /// Wait until the transmitter is idle, including the stop bits of the last
+                    /// byte.
+                    async fn flush(&mut self) -> Result<(), Self::Error> {
+                        let uart = unsafe { &(*$UARTX::ptr()) };
+
+                        // The TX interrupt is raised when the last bit left the serializer.
+                        uart.ctl().modify(|_, w| w.eot().set_bit());
+
+                        core::future::poll_fn(|cx: &mut Context<'_>| {
+                            [<$UARTX:upper _TX_WAKER>].register(cx.waker());
+                            [<listen_ $uartX>](Event::Txe);
+                            unsafe { NVIC::unmask(interrupt::[<$UARTX:upper>]) };
+
+                            if uart.fr().read().busy().bit_is_set() {
+                                return Poll::Pending;
+                            }
+
+                            [<unlisten_ $uartX>](Event::Txe);
+                            [<$UARTX:upper _TX_WAKER>].clear();
+
+                            Poll::Ready(Ok(()))
+                        })
+                        .await
+                    }
+                }
+            }
+
             impl ErrorType for Rx<$UARTX> {
                 type Error = core::convert::Infallible;
             }
@@ -1,7 +1,9 @@
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
+use core::task::Waker;
 
 use cortex_m::peripheral::NVIC;
 
+use crate::interrupt::InterruptExt;
 use crate::sys_ctrl::ClockConfig;
 use crate::{pac::Smwdthrosc, sys_ctrl::ClockDiv};
 
@@ -14,13 +16,31 @@ pub trait SleepTimerExt {
 #[derive(Debug)]
 pub struct SleepTimer {
     smwdthrosc: Smwdthrosc,
+    /// The value of the sleep timer at which the alarm fires.
+    alarm: Cell<Option<u32>>,
+}
+
+/// The result of programming the compare value of the sleep timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmStatus {
+    /// The compare value is programmed and the `SM_TIMER` interrupt fires when it is reached.
+    Armed,
+    /// The value already passed, or was too close to be programmed reliably.
+    Elapsed,
+}
+
+impl InterruptExt for SleepTimer {
+    const INTERRUPT: cc2538_pac::Interrupt = cc2538_pac::Interrupt::SM_TIMER;
 }
 
 impl SleepTimerExt for Smwdthrosc {
     type Parts = SleepTimer;
 
     fn split(self) -> Self::Parts {
-        SleepTimer { smwdthrosc: self }
+        SleepTimer {
+            smwdthrosc: self,
+            alarm: Cell::new(None),
+        }
     }
 }
 
@@ -39,9 +59,16 @@ impl SleepTimer {
         //})
     }
 
+    /// Write the compare value of the sleep timer.
+    ///
+    /// The compare only matches when it is loaded before the sleep timer reaches it. Nothing
+    /// is written when `t` is less than [`MIN_TICKS_AHEAD`] ticks away, and
+    /// [`AlarmStatus::Elapsed`] is returned, also when `t` passed while it was being loaded.
     #[inline]
-    fn set_ticks(&self, t: u32) {
-        debug_assert!(t > self.now());
+    fn set_ticks(&self, t: u32) -> AlarmStatus {
+        if (t.wrapping_sub(self.now()) as i32) < MIN_TICKS_AHEAD as i32 {
+            return AlarmStatus::Elapsed;
+        }
 
         while self.smwdthrosc.stload().read().stload().bit_is_clear() {}
 
@@ -60,19 +87,263 @@ impl SleepTimer {
                 .write(|w| w.st0().bits((t & 0xff) as u8));
         });
 
+        if (self.now().wrapping_sub(t) as i32) >= 0 {
+            AlarmStatus::Elapsed
+        } else {
+            AlarmStatus::Armed
+        }
+    }
+
+    /// Set an alarm at an absolute value of the sleep timer and return immediately.
+    ///
+    /// The `SM_TIMER` interrupt is unmasked and fires when the sleep timer reaches `ticks`,
+    /// which also wakes up the chip from a power mode. Use [`SleepTimer::alarm_elapsed`] to
+    /// poll the alarm instead.
+    ///
+    /// When `ticks` already passed or is too close to be programmed, this waits until the
+    /// sleep timer reaches `ticks` and pends the interrupt, such that it still fires right away,
+    /// and returns [`AlarmStatus::Elapsed`].
+    #[inline]
+    pub fn set_alarm(&self, ticks: u32) -> AlarmStatus {
+        self.alarm.set(Some(ticks));
+        let status = self.set_ticks(ticks);
+
+        if status == AlarmStatus::Elapsed {
+            self.wait_absolute(ticks);
+            NVIC::pend(cc2538_pac::Interrupt::SM_TIMER);
+        }
+
         unsafe {
             NVIC::unmask(cc2538_pac::Interrupt::SM_TIMER);
         }
+
+        status
+    }
+
+    /// Cancel the alarm and mask the `SM_TIMER` interrupt.
+    #[inline]
+    pub fn cancel_alarm(&self) {
+        self.alarm.set(None);
+        NVIC::mask(cc2538_pac::Interrupt::SM_TIMER);
+    }
+
+    /// Check if the sleep timer reached the value of the last alarm.
+    ///
+    /// Returns `false` when no alarm was set.
+    #[inline]
+    pub fn alarm_elapsed(&self) -> bool {
+        match self.alarm.get() {
+            // The difference is interpreted as signed, such that wrapping is handled.
+            Some(alarm) => self.now().wrapping_sub(alarm) as i32 >= 0,
+            None => false,
+        }
     }
 
+    /// Block for `ticks` periods of the sleep timer.
     #[inline]
     pub fn wait_relative(&self, ticks: u32) {
-        let ticks = self.now() + ticks;
-        self.set_ticks(ticks);
+        let ticks = self.now().wrapping_add(ticks);
+        self.wait_absolute(ticks);
     }
 
+    /// Block until the sleep timer reaches `ticks`.
+    ///
+    /// This polls the sleep timer and does not need the `SM_TIMER` interrupt.
     #[inline]
     pub fn wait_absolute(&self, ticks: u32) {
-        self.set_ticks(ticks);
+        while (self.now().wrapping_sub(ticks) as i32) < 0 {}
+    }
+}
+
+/// The minimum amount of ticks between now and the compare value of the sleep timer.
+///
+/// The compare value only triggers reliably when it is written a few ticks in the future, this
+/// is the same margin as the minimal safe schedule of contiki-ng.
+const MIN_TICKS_AHEAD: u32 = 8;
+
+/// What to do when a deadline of the [`TimerWheel`] is reached.
+#[derive(Debug, Clone)]
+pub enum TimerAction {
+    /// Wake the task.
+    Wake(Waker),
+    /// Call the function, from the context in which [`TimerWheel::on_interrupt`] is called.
+    Call(fn()),
+}
+
+impl TimerAction {
+    fn fire(self) {
+        match self {
+            TimerAction::Wake(waker) => waker.wake(),
+            TimerAction::Call(f) => f(),
+        }
+    }
+}
+
+/// The identifier of a scheduled deadline, used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerId(u32);
+
+/// The [`TimerWheel`] has no free entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerWheelFull;
+
+#[derive(Debug, Clone)]
+struct TimerEntry {
+    id: TimerId,
+    deadline: u32,
+    action: TimerAction,
+}
+
+/// Schedules up to `N` deadlines with the compare of a single [`SleepTimer`].
+///
+/// The compare of the sleep timer is always programmed for the nearest deadline. The
+/// `SM_TIMER` interrupt handler must call [`TimerWheel::on_interrupt`], which runs the actions
+/// of the deadlines that passed and re-arms the compare for the next one:
+///
+/// ```ignore
+/// static WHEEL: Mutex<RefCell<TimerWheel<4>>> = Mutex::new(RefCell::new(TimerWheel::new()));
+///
+/// #[interrupt]
+/// fn SM_TIMER() {
+///     free(|cs| WHEEL.borrow(cs).borrow_mut().on_interrupt(sleep_timer));
+/// }
+/// ```
+///
+/// Deadlines are absolute values of the sleep timer. They are compared relative to the current
+/// value, such that the wrapping of the timer is handled, as long as a deadline is less than
+/// 2^31 ticks (about 18 hours) away.
+#[derive(Debug)]
+pub struct TimerWheel<const N: usize> {
+    entries: [Option<TimerEntry>; N],
+    next_id: u32,
+}
+
+impl<const N: usize> Default for TimerWheel<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TimerWheel<N> {
+    const EMPTY: Option<TimerEntry> = None;
+
+    /// Create an empty timer wheel.
+    pub const fn new() -> Self {
+        Self {
+            entries: [Self::EMPTY; N],
+            next_id: 0,
+        }
+    }
+
+    /// Schedule `action` at the absolute sleep timer value `deadline`.
+    ///
+    /// The `SM_TIMER` interrupt is pended, such that the compare is re-programmed from
+    /// [`TimerWheel::on_interrupt`]. A deadline that already passed is run from there as well.
+    pub fn schedule(
+        &mut self,
+        deadline: u32,
+        action: TimerAction,
+    ) -> Result<TimerId, TimerWheelFull> {
+        let slot = self
+            .entries
+            .iter_mut()
+            .find(|e| e.is_none())
+            .ok_or(TimerWheelFull)?;
+
+        let id = TimerId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        *slot = Some(TimerEntry {
+            id,
+            deadline,
+            action,
+        });
+
+        Self::pend_rearm();
+
+        Ok(id)
+    }
+
+    /// Cancel a scheduled deadline.
+    ///
+    /// The `SM_TIMER` interrupt is pended, such that the compare is re-programmed from
+    /// [`TimerWheel::on_interrupt`]. Returns `false` when the deadline was already run or
+    /// canceled.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| matches!(e, Some(entry) if entry.id == id));
+
+        match entry {
+            Some(entry) => {
+                *entry = None;
+                Self::pend_rearm();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return `true` when no deadline is scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(|e| e.is_none())
+    }
+
+    /// Run the actions of the deadlines that passed and program the compare for the next one.
+    ///
+    /// This must be called from the `SM_TIMER` interrupt handler.
+    pub fn on_interrupt(&mut self, timer: &SleepTimer) {
+        self.rearm(timer);
+    }
+
+    /// Pend the `SM_TIMER` interrupt, such that the actions are only run from the interrupt
+    /// handler and never from the context that schedules or cancels a deadline.
+    fn pend_rearm() {
+        NVIC::pend(cc2538_pac::Interrupt::SM_TIMER);
+        unsafe {
+            NVIC::unmask(cc2538_pac::Interrupt::SM_TIMER);
+        }
+    }
+
+    /// Run the deadlines that passed or are too close to be programmed, and program the
+    /// compare of the sleep timer for the nearest remaining deadline.
+    fn rearm(&mut self, timer: &SleepTimer) {
+        loop {
+            let now = timer.now();
+
+            // The deadline with the smallest (signed) distance from now.
+            let nearest = self
+                .entries
+                .iter_mut()
+                .filter(|e| e.is_some())
+                .min_by_key(|e| e.as_ref().unwrap().deadline.wrapping_sub(now) as i32);
+
+            let nearest = match nearest {
+                Some(nearest) => nearest,
+                None => {
+                    timer.cancel_alarm();
+                    return;
+                }
+            };
+
+            let deadline = nearest.as_ref().unwrap().deadline;
+            let ahead = deadline.wrapping_sub(now) as i32;
+
+            if ahead < MIN_TICKS_AHEAD as i32 {
+                // The deadline passed, or will pass before the compare can be programmed.
+                if ahead > 0 {
+                    while (timer.now().wrapping_sub(deadline) as i32) < 0 {}
+                }
+
+                nearest.take().unwrap().action.fire();
+                continue;
+            }
+
+            // When the deadline passes while it is programmed, the interrupt is pended and the
+            // deadline is run from the next call to `on_interrupt`.
+            timer.set_alarm(deadline);
+            return;
+        }
     }
 }
@@ -2,8 +2,12 @@ use core::marker::PhantomData;
 
 use cc2538_pac::{Ssi0, Ssi1};
 
+use crate::interrupt::InterruptExt;
+use crate::pac::Interrupt;
 use crate::sys_ctrl::ClockConfig;
 
+pub use crate::hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
+
 pub enum ClockSource {
     /// The baud clock is determined by the SYS Div setting.
     /// The SSI system clock is determined by the SYS Div setting.
@@ -19,16 +23,55 @@ pub enum ClockSource {
     IoDivBaudClock = 0b101,
 }
 
+/// The frame format of the SSI.
+///
+/// The clock polarity and phase (SPO and SPH) only have a meaning for the Motorola SPI frame
+/// format, which is why only [`FrameFormat::Spi`] carries a [`Mode`]. For the TI synchronous
+/// serial and Microwire frame formats, SPO and SPH are cleared.
 pub enum FrameFormat {
-    Spi,
+    /// Motorola SPI frame format, in any of the 4 SPI modes.
+    Spi(Mode),
+    /// TI synchronous serial frame format.
     TexasInstrumentSyncSerial,
+    /// National Microwire frame format.
     Microwave,
 }
 
+impl FrameFormat {
+    /// The value of the FRF field.
+    const fn frf(&self) -> u8 {
+        match self {
+            FrameFormat::Spi(_) => 0b00,
+            FrameFormat::TexasInstrumentSyncSerial => 0b01,
+            FrameFormat::Microwave => 0b10,
+        }
+    }
+}
+
+/// The depth of the transmit and receive FIFOs, in frames.
+pub const FIFO_DEPTH: usize = 8;
+
+/// SSI interrupt events.
+pub enum Event {
+    /// The transmit FIFO is half empty or less, i.e. at least [`FIFO_DEPTH`] / 2 frames can be
+    /// written.
+    TxFifoHalfEmpty,
+    /// The receive FIFO is half full or more.
+    RxFifoHalfFull,
+    /// Data is in the receive FIFO and was not read for 32 bit periods.
+    RxTimeout,
+    /// A frame was received while the receive FIFO was full.
+    RxOverrun,
+}
+
 macro_rules! spi {
     (
-        $spi:ident
+        $spi:ident, $interrupt:ident
     ) => {
+        impl<STATE> InterruptExt for Spi<$spi, STATE> {
+            const INTERRUPT: Interrupt = Interrupt::$interrupt;
+        }
+
         impl Spi<$spi, Disabled> {
             pub fn as_master(self) -> Self {
                 unsafe { self.ssi.cr1().write_with_zero(|w| w) };
@@ -47,18 +90,94 @@ macro_rules! spi {
                 self
             }
 
+            /// Connect the output of the transmit serial shifter to the input of the receive
+            /// serial shifter, such that the SSI can be tested without external wiring.
+            ///
+            /// Loopback must be set before [`Self::enable`] and after [`Self::as_master`], since
+            /// the latter clears the control register.
+            pub fn enable_loopback(self) -> Self {
+                self.ssi.cr1().modify(|_, w| w.lbm().set_bit());
+                self
+            }
+
+            /// Use the normal serial port operation, which is the default.
+            pub fn disable_loopback(self) -> Self {
+                self.ssi.cr1().modify(|_, w| w.lbm().clear_bit());
+                self
+            }
+
             pub fn set_clock_source(self, clock_source: ClockSource) -> Self {
                 unsafe { self.ssi.cc().modify(|_, w| w.cs().bits(clock_source as u8)) };
                 self
             }
 
+            /// Set the frame format, including the clock polarity and phase for SPI.
+            pub fn set_frame_format(self, frame_format: FrameFormat) -> Self {
+                let (spo, sph) = match &frame_format {
+                    FrameFormat::Spi(mode) => (
+                        mode.polarity == Polarity::IdleHigh,
+                        mode.phase == Phase::CaptureOnSecondTransition,
+                    ),
+                    _ => (false, false),
+                };
+
+                unsafe {
+                    self.ssi.cr0().modify(|_, w| {
+                        w.frf()
+                            .bits(frame_format.frf())
+                            .spo()
+                            .bit(spo)
+                            .sph()
+                            .bit(sph)
+                    })
+                };
+                self
+            }
+
+            /// Set the bit rate of the SPI clock.
+            ///
+            /// The baud clock is taken from the SYS or IO divider, depending on the
+            /// [`ClockSource`] set with [`Self::set_clock_source`], so set the clock source
+            /// first. The bit rate is `baud clock / (CPSDVSR * (1 + SCR))`, and both divisors
+            /// are searched for the highest bit rate that does not exceed `bit_rate`. When
+            /// `bit_rate` is too low to be reached (including 0), the slowest possible clock is
+            /// used. The achieved bit rate is returned by [`Spi::clock_rate`].
             pub fn set_bit_rate(self, bit_rate: u32, clock_config: ClockConfig) -> Self {
-                let div = 2 * bit_rate;
-                let scr = clock_config.sys_freq().div_ceil(div);
-                let scr = core::cmp::min(core::cmp::max(scr, 1), 256) - 1;
+                // A bit rate of 0 can not be reached either, and would divide by zero below.
+                let bit_rate = bit_rate.max(1);
+
+                // Bit 2 of the clock source selects the IO divider for the baud clock.
+                let freq = if self.ssi.cc().read().cs().bits() & 0b100 != 0 {
+                    clock_config.io_freq()
+                } else {
+                    clock_config.sys_freq()
+                };
+
+                // The slowest possible configuration, used when nothing better is found.
+                let mut best = (254, 255, freq / (254 * 256));
+
+                for cpsdvsr in (2..=254u32).step_by(2) {
+                    let scr = freq.div_ceil(cpsdvsr.saturating_mul(bit_rate)).max(1) - 1;
+                    if scr > 255 {
+                        continue;
+                    }
+
+                    let rate = freq / (cpsdvsr * (scr + 1));
+                    if rate > best.2 {
+                        best = (cpsdvsr, scr, rate);
+                    }
+
+                    if rate == bit_rate {
+                        break;
+                    }
+                }
+
+                let (cpsdvsr, scr, _) = best;
 
                 unsafe {
-                    self.ssi.cpsr().modify(|_, w| w.cpsdvsr().bits(2));
+                    self.ssi
+                        .cpsr()
+                        .modify(|_, w| w.cpsdvsr().bits(cpsdvsr as u8));
                 }
                 unsafe {
                     self.ssi.cr0().modify(|_, w| w.scr().bits(scr as u8));
@@ -67,9 +186,26 @@ macro_rules! spi {
                 self
             }
 
+            /// Set the number of bits per frame, from 4 to 16.
+            ///
+            /// The default is 8 bits. The byte methods ([`Spi::read`], [`Spi::write`], ...) only
+            /// use the lower 8 bits of every frame, use [`Spi::write16`] and
+            /// [`Spi::transfer16`] for frames larger than 8 bits.
+            ///
+            /// # Panics
+            /// Panics when `bits` is not in 4..=16.
+            pub fn set_data_size(self, bits: u8) -> Self {
+                assert!((4..=16).contains(&bits));
+                unsafe { self.ssi.cr0().modify(|_, w| w.dss().bits(bits - 1)) };
+                self
+            }
+
             pub fn enable(self) -> Spi<$spi, Enabled> {
-                // 8-bit data transfer
-                unsafe { self.ssi.cr0().modify(|_, w| w.dss().bits(0b0111)) };
+                // 8-bit data transfer, unless the data size is set (values below 3 are
+                // reserved).
+                if self.ssi.cr0().read().dss().bits() < 0b0011 {
+                    unsafe { self.ssi.cr0().modify(|_, w| w.dss().bits(0b0111)) };
+                }
                 self.ssi.cr1().modify(|_, w| w.sse().set_bit());
                 Spi {
                     ssi: self.ssi,
@@ -78,6 +214,39 @@ macro_rules! spi {
             }
         }
 
+        impl<STATE> Spi<$spi, STATE> {
+            /// Release the SSI peripheral.
+            ///
+            /// The SSI is disabled first, such that the pins can be reconfigured.
+            pub fn free(self) -> $spi {
+                self.ssi.cr1().modify(|_, w| w.sse().clear_bit());
+                self.ssi
+            }
+
+            /// Return the bit rate of the SPI clock, computed from the registers.
+            ///
+            /// This is `baud clock / (CPSDVSR * (1 + SCR))`, with the baud clock taken from the
+            /// SYS or IO divider depending on the [`ClockSource`]. Returns 0 when the prescaler
+            /// is not set yet.
+            pub fn clock_rate(&self, clock_config: &ClockConfig) -> u32 {
+                // Bit 2 of the clock source selects the IO divider for the baud clock.
+                let freq = if self.ssi.cc().read().cs().bits() & 0b100 != 0 {
+                    clock_config.io_freq()
+                } else {
+                    clock_config.sys_freq()
+                };
+
+                let cpsdvsr = self.ssi.cpsr().read().cpsdvsr().bits() as u32;
+                let scr = self.ssi.cr0().read().scr().bits() as u32;
+
+                if cpsdvsr == 0 {
+                    return 0;
+                }
+
+                freq / (cpsdvsr * (scr + 1))
+            }
+        }
+
         impl Spi<$spi, Enabled> {
             pub fn is_busy(&self) -> bool {
                 self.ssi.sr().read().bsy().bit_is_set()
@@ -103,6 +272,44 @@ macro_rules! spi {
                 (self.ssi.dr().read().bits() & 0x00ff) as u16
             }
 
+            /// Read the bytes that are in the receive FIFO, without clocking out anything.
+            ///
+            /// Stops when the receive FIFO is empty or when `buf` is full, even if more bytes
+            /// arrive. Returns the number of bytes read.
+            pub fn read_available(&self, buf: &mut [u8]) -> usize {
+                let mut n = 0;
+                while n < buf.len() && !self.is_receive_fifo_empty() {
+                    buf[n] = self.read_data() as u8;
+                    n += 1;
+                }
+                n
+            }
+
+            /// Read bytes, clocking out zeros.
+            pub fn read(&self, buf: &mut [u8]) {
+                self.read_with_fill(buf, 0x00);
+            }
+
+            /// Read bytes, clocking out `fill` for every byte that is read.
+            ///
+            /// Bytes left in the receive FIFO (e.g. received during [`Self::write`]) are
+            /// discarded first.
+            pub fn read_with_fill(&self, buf: &mut [u8], fill: u8) {
+                while !self.is_receive_fifo_empty() {
+                    let _ = self.read_data();
+                }
+
+                for b in buf.iter_mut() {
+                    while self.is_send_fifo_full() {}
+                    unsafe {
+                        self.ssi.dr().write(|w| w.data().bits(fill as u16));
+                    }
+
+                    while self.is_receive_fifo_empty() {}
+                    *b = self.read_data() as u8;
+                }
+            }
+
             pub fn write(&self, data: &[u8]) {
                 for b in data.iter() {
                     while self.is_send_fifo_full() {}
@@ -111,6 +318,98 @@ macro_rules! spi {
                     }
                 }
             }
+
+            /// Write as many bytes as fit in the transmit FIFO, without blocking.
+            ///
+            /// Returns the number of bytes written, which is at most [`FIFO_DEPTH`]. Together
+            /// with [`Event::TxFifoHalfEmpty`], a bigger buffer can be sent from the interrupt
+            /// handler by writing the rest of the buffer on every interrupt, until all bytes
+            /// are written. That interrupt stays pending as long as the FIFO is half empty, so
+            /// stop listening for it after the last byte.
+            pub fn try_write(&self, data: &[u8]) -> usize {
+                let mut n = 0;
+                while n < data.len() && !self.is_send_fifo_full() {
+                    unsafe {
+                        self.ssi.dr().write(|w| w.data().bits(data[n] as u16));
+                    }
+                    n += 1;
+                }
+                n
+            }
+
+            /// Start listening for an interrupt event.
+            ///
+            /// The SSI interrupt is unmasked in the NVIC as well.
+            pub fn listen(&mut self, event: Event) {
+                self.ssi.im().modify(|_, w| match event {
+                    Event::TxFifoHalfEmpty => w.txim().set_bit(),
+                    Event::RxFifoHalfFull => w.rxim().set_bit(),
+                    Event::RxTimeout => w.rtim().set_bit(),
+                    Event::RxOverrun => w.rorim().set_bit(),
+                });
+                self.unmask_interrupt();
+            }
+
+            /// Stop listening for an interrupt event.
+            ///
+            /// The SSI interrupt is masked in the NVIC when no event is listened to anymore.
+            pub fn unlisten(&mut self, event: Event) {
+                self.ssi.im().modify(|_, w| match event {
+                    Event::TxFifoHalfEmpty => w.txim().clear_bit(),
+                    Event::RxFifoHalfFull => w.rxim().clear_bit(),
+                    Event::RxTimeout => w.rtim().clear_bit(),
+                    Event::RxOverrun => w.rorim().clear_bit(),
+                });
+                if self.ssi.im().read().bits() == 0 {
+                    self.mask_interrupt();
+                }
+            }
+
+            /// Return the number of bits per frame, see [`Spi::set_data_size`].
+            pub fn data_size(&self) -> u8 {
+                self.ssi.cr0().read().dss().bits() + 1
+            }
+
+            /// The mask of the bits of a frame.
+            fn data_mask(&self) -> u16 {
+                (((1u32 << self.data_size()) - 1) & 0xffff) as u16
+            }
+
+            /// Write frames of up to 16 bits.
+            ///
+            /// Every word is masked to the data size, the bits above it are not sent.
+            pub fn write16(&self, data: &[u16]) {
+                let mask = self.data_mask();
+                for w in data.iter() {
+                    while self.is_send_fifo_full() {}
+                    unsafe {
+                        self.ssi.dr().write(|dr| dr.data().bits(*w & mask));
+                    }
+                }
+            }
+
+            /// Write frames of up to 16 bits, replacing every word with the frame that is
+            /// received at the same time.
+            ///
+            /// Every word is masked to the data size before it is sent. Frames left in the
+            /// receive FIFO are discarded first.
+            pub fn transfer16(&mut self, data: &mut [u16]) {
+                let mask = self.data_mask();
+
+                while !self.is_receive_fifo_empty() {
+                    let _ = self.ssi.dr().read();
+                }
+
+                for w in data.iter_mut() {
+                    while self.is_send_fifo_full() {}
+                    unsafe {
+                        self.ssi.dr().write(|dr| dr.data().bits(*w & mask));
+                    }
+
+                    while self.is_receive_fifo_empty() {}
+                    *w = self.ssi.dr().read().data().bits() & mask;
+                }
+            }
         }
     };
 }
@@ -137,6 +436,11 @@ impl SpiSsi0Ext for Ssi0 {
     type Parts = Spi<Self, Disabled>;
 
     fn take(self) -> Self::Parts {
+        debug_assert!(
+            crate::sys_ctrl::is_ssi0_clock_enabled(),
+            "SSI0 clock not enabled, call `enable_ssi0_in_active_mode` first"
+        );
+
         // Disble the SSI
         self.cr1().modify(|_, w| w.sse().clear_bit());
 
@@ -151,6 +455,11 @@ impl SpiSsi1Ext for Ssi1 {
     type Parts = Spi<Self, Disabled>;
 
     fn take(self) -> Self::Parts {
+        debug_assert!(
+            crate::sys_ctrl::is_ssi1_clock_enabled(),
+            "SSI1 clock not enabled, call `enable_ssi1_in_active_mode` first"
+        );
+
         // Disble the SSI
         self.cr1().modify(|_, w| w.sse().clear_bit());
 
@@ -161,5 +470,5 @@ impl SpiSsi1Ext for Ssi1 {
     }
 }
 
-spi!(Ssi0);
-spi!(Ssi1);
+spi!(Ssi0, SSI0);
+spi!(Ssi1, SSI1);
@@ -2,9 +2,15 @@
 
 use paste::paste;
 
-use core::{marker::PhantomData, time::Duration};
+use core::{
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
 
 use cortex_m::asm;
+use cortex_m::peripheral::SCB;
 
 use crate::pac::{sys_ctrl, SysCtrl as SysCtrlPac};
 use crate::time::*;
@@ -42,6 +48,100 @@ impl ClockDiv {
     }
 }
 
+/// The power modes of the CC2538.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerMode {
+    /// Only the clock of the CPU is stopped.
+    Pm0 = 0b00,
+    /// The system clock is stopped, all registers are retained.
+    Pm1 = 0b01,
+    /// The 32 MHz oscillator is powered down, not all peripheral registers are retained.
+    Pm2 = 0b10,
+    /// Only an external interrupt can wake up the chip, not all peripheral registers are
+    /// retained.
+    Pm3 = 0b11,
+}
+
+/// The amount of times the chip woke up from a power mode in which not all peripheral registers
+/// are retained.
+static RETENTION_LOST: AtomicU32 = AtomicU32::new(0);
+
+/// Return the amount of times the chip woke up from PM2 or PM3.
+///
+/// Drivers compare this with the value they saw before to find out if they need to restore
+/// their registers.
+pub(crate) fn retention_lost_count() -> u32 {
+    RETENTION_LOST.load(Ordering::Acquire)
+}
+
+/// The reason recorded by [`reset_on_panic!`](crate::reset_on_panic) before resetting.
+pub const PANIC_RESET_REASON: u32 = 1;
+
+/// Marks that [`RESET_REASON`] was written by [`software_reset`] and not left over from a power
+/// on reset.
+const RESET_REASON_MAGIC: u32 = 0x5245_5354;
+
+/// The magic word and the reason of the last software reset.
+///
+/// This is placed in the `.uninit` section, which is not zeroed by the startup code, such that
+/// it survives a warm reset.
+#[link_section = ".uninit.RESET_REASON"]
+static mut RESET_REASON: MaybeUninit<[u32; 2]> = MaybeUninit::uninit();
+
+/// Reset the chip with a warm reset, recording `reason`.
+///
+/// The reason can be read back after the reset with [`take_software_reset_reason`].
+pub fn software_reset(reason: u32) -> ! {
+    unsafe {
+        core::ptr::write_volatile(
+            core::ptr::addr_of_mut!(RESET_REASON) as *mut [u32; 2],
+            [RESET_REASON_MAGIC, reason],
+        );
+    }
+    asm::dsb();
+
+    unsafe { &*SysCtrlPac::ptr() }
+        .pwrdbg()
+        .write(|w| w.force_warm_reset().set_bit());
+
+    // The warm reset should happen immediately, fall back to a system reset request.
+    SCB::sys_reset()
+}
+
+/// Return the reason given to [`software_reset`] when that caused the last reset.
+///
+/// The reason is cleared, such that a following reset with another cause is not reported as a
+/// software reset.
+pub fn take_software_reset_reason() -> Option<u32> {
+    let ptr = core::ptr::addr_of_mut!(RESET_REASON) as *mut [u32; 2];
+    let [magic, reason] = unsafe { core::ptr::read_volatile(ptr) };
+
+    if magic != RESET_REASON_MAGIC {
+        return None;
+    }
+
+    unsafe { core::ptr::write_volatile(ptr, [0, 0]) };
+    Some(reason)
+}
+
+/// Install a panic handler that resets the chip with [`software_reset`].
+///
+/// The reason is recorded as [`PANIC_RESET_REASON`]. This is opt-in: only invoke this macro once
+/// in the binary and do not link another panic handler, like `panic-rtt-target`.
+///
+/// ```ignore
+/// cc2538_hal::reset_on_panic!();
+/// ```
+#[macro_export]
+macro_rules! reset_on_panic {
+    () => {
+        #[panic_handler]
+        fn panic(_info: &core::panic::PanicInfo) -> ! {
+            $crate::sys_ctrl::software_reset($crate::sys_ctrl::PANIC_RESET_REASON)
+        }
+    };
+}
+
 pub struct Unconfigured;
 pub struct Frozen;
 
@@ -51,6 +151,15 @@ pub struct SysCtrl<STATE> {
     _state: PhantomData<STATE>,
 }
 
+impl<STATE> SysCtrl<STATE> {
+    /// Release the SYS_CTRL peripheral.
+    ///
+    /// The registers are not reset, so the clocks keep their configuration.
+    pub fn free(self) -> SysCtrlPac {
+        self.sys_ctrl
+    }
+}
+
 impl ClockConfig {
     pub const fn sys_freq(&self) -> u32 {
         self.sys_div.as_freq()
@@ -125,6 +234,16 @@ macro_rules! impl_sys_ctrl {
             }
         }
 
+        $(
+        /// Return `true` when the clock of the peripheral is enabled in active mode.
+        ///
+        /// This is used by the peripheral drivers to check that the clock was enabled before
+        /// accessing the registers, which otherwise silently read zeros.
+        pub(crate) fn [<is_ $new_name _clock_enabled>]() -> bool {
+            unsafe { &*SysCtrlPac::ptr() }.$active_reg().read().$name().bit_is_set()
+        }
+        )+
+
         impl<STATE> SysCtrl<STATE> {
             $(
             pub fn [<enable_ $new_name _in_active_mode>](&mut self) {
@@ -159,6 +278,16 @@ macro_rules! impl_sys_ctrl {
             )+
 
             $(
+            /// Reset the peripheral, by asserting and de-asserting its reset.
+            ///
+            /// This is the recommended way to reset a peripheral: with the separate methods,
+            /// forgetting to clear the reset keeps the peripheral in reset. The reset is
+            /// synchronous, so no delay is needed in between.
+            pub fn [<pulse_reset_ $new_reset_name>](&mut self) {
+                self.[<reset_ $new_reset_name>]();
+                self.[<clear_reset_ $new_reset_name>]();
+            }
+
             pub fn [<reset_ $new_reset_name>](&mut self) {
                 self.sys_ctrl.$reset_reg().modify(|_, w| w.$reset_name().set_bit());
             }
@@ -255,3 +384,28 @@ impl_sys_ctrl!(
         (aes = aes -> srsec),
     ]
 );
+
+impl SysCtrl<Frozen> {
+    /// Enter a power mode and wait for an interrupt to wake up.
+    ///
+    /// Waking up from PM2 or PM3 is recorded, such that drivers depending on registers that are
+    /// not retained (like the AES engine) restore them before their next operation.
+    pub fn enter_power_mode(&mut self, scb: &mut SCB, mode: PowerMode) {
+        unsafe { self.sys_ctrl.pmctl().write(|w| w.pm().bits(mode as u8)) };
+
+        if mode == PowerMode::Pm0 {
+            scb.clear_sleepdeep();
+        } else {
+            scb.set_sleepdeep();
+        }
+
+        asm::dsb();
+        asm::wfi();
+
+        if matches!(mode, PowerMode::Pm2 | PowerMode::Pm3) {
+            RETENTION_LOST.fetch_add(1, Ordering::AcqRel);
+        }
+
+        scb.clear_sleepdeep();
+    }
+}
@@ -3,10 +3,11 @@
 use core::future::Future;
 use core::marker::PhantomData;
 use core::pin::Pin;
-use core::sync::atomic::{self, AtomicBool, Ordering};
-use core::task::{Context, Poll, Waker};
+use core::sync::atomic::{self, AtomicBool, AtomicU32, Ordering};
+use core::task::{Context, Poll};
 use core::time::Duration;
 
+use crate::interrupt::{InterruptExt, WakerCell};
 use crate::pac;
 use cortex_m::peripheral::NVIC;
 use cortex_m_rt::interrupt;
@@ -66,6 +67,34 @@ enum Config {
     Timer16 = 0x4,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimerError {
+    /// The duration does not fit in the 16-bit timer with an 8-bit prescaler.
+    DurationTooLong,
+}
+
+/// Compute the prescaler and the start value of a 16-bit timer for a duration.
+///
+/// The timer clock is divided by `prescaler + 1`, and the timer counts `start value` ticks of
+/// the divided clock. All intermediate values are computed in nanoseconds times Hz, such that
+/// IO clocks below 1 MHz and durations from microseconds to seconds are handled correctly.
+pub(crate) fn prescaler_and_start_value(
+    dur: Duration,
+    io_freq: u32,
+) -> Result<(u8, u16), TimerError> {
+    let ticks = (dur.as_nanos() * io_freq as u128).div_ceil(1_000_000_000);
+
+    // The smallest prescaler for which the start value fits in 16 bits.
+    let prescaler = ticks.saturating_sub(1) / u16::MAX as u128;
+    if prescaler > u8::MAX as u128 {
+        return Err(TimerError::DurationTooLong);
+    }
+
+    let start_value = ticks / (prescaler + 1);
+
+    Ok((prescaler as u8, start_value as u16))
+}
+
 /// State of the timer where the timer is uninitialised.
 pub struct Uninit;
 /// State of the timer where the timer is configured.
@@ -164,6 +193,10 @@ macro_rules! timer {
                     _type: PhantomData<TYPE>,
                 }
 
+                impl<STATE, TYPE> InterruptExt for [<Timer $sub_type>]<STATE, TYPE> {
+                    const INTERRUPT: pac::Interrupt = pac::Interrupt::[<$TIMERX:upper $sub_type>];
+                }
+
                 impl [<Timer $sub_type>]<Uninit, NotSpecified> {
                     /// Disable the timer.
                     pub fn disable(self, timer: &mut $type) -> Self {
@@ -266,6 +299,10 @@ macro_rules! timer {
                     }
 
                     /// Listen to a specific interrupt.
+                    ///
+                    /// The interrupt of the timer is not unmasked in the NVIC: the HAL owns the
+                    /// interrupt handler, which only serves the async `wait` and the ticker. Poll
+                    /// the timer instead.
                     pub fn listen(&mut self, event: Event) {
                         let timer = unsafe { &* cc2538_pac::$TIMERX::ptr() };
                         self.mr.[<t $sub_type:lower mr>]().modify(|_, w| w.[<t $sub_type:lower mie>]().set_bit());
@@ -308,10 +345,131 @@ macro_rules! timer {
                     pub fn disable_snapshot_mode(self) -> Self {
                         todo!();
                     }
+
+                    /// Start the timer, ticking every `period`.
+                    ///
+                    /// The timer reloads itself in hardware, the time-out interrupt only counts
+                    /// the ticks and wakes up the task waiting for the next tick.
+                    ///
+                    /// Returns [`TimerError::DurationTooLong`] when the period does not fit in
+                    /// the timer.
+                    pub fn start_ticking(
+                        mut self,
+                        period: Duration,
+                        config: &ClockConfig,
+                    ) -> Result<[<Ticker $sub_type>], TimerError> {
+                        let (prescaler, start_value) =
+                            prescaler_and_start_value(period, config.io_freq())?;
+
+                        self.set_count_direction(CountDirection::Down);
+                        self.set_prescaler(prescaler);
+                        self.set_start_value(start_value);
+                        self.listen(Event::TimeOut);
+                        let mut timer = self.configure();
+
+                        timer.clear_interrupts();
+
+                        [<$TIMERX:upper $sub_type _TICKS>].store(0, Ordering::Relaxed);
+                        [<$TIMERX:upper $sub_type _PERIODIC>].store(true, Ordering::Relaxed);
+                        atomic::compiler_fence(Ordering::Release);
+                        unsafe { NVIC::unmask(pac::Interrupt::[<$TIMERX:upper $sub_type>]) };
+
+                        timer.enable();
+
+                        Ok([<Ticker $sub_type>] {
+                            timer,
+                            skip_missed: false,
+                        })
+                    }
+                }
+
+                /// A periodic timer that is running, created with `start_ticking`.
+                pub struct [<Ticker $sub_type>] {
+                    timer: [<Timer $sub_type>]<Configured, PeriodicTimer>,
+                    skip_missed: bool,
+                }
+
+                impl [<Ticker $sub_type>] {
+                    /// Set whether ticks that were missed because [`Self::tick`] was called too
+                    /// late are skipped.
+                    ///
+                    /// By default, no tick is skipped: every missed tick makes a following call
+                    /// to [`Self::tick`] return immediately, such that the ticks catch up.
+                    pub fn skip_missed_ticks(&mut self, skip: bool) {
+                        self.skip_missed = skip;
+                    }
+
+                    /// Wait for the next tick.
+                    ///
+                    /// Returns the number of ticks that are consumed. This is always 1, unless
+                    /// missed ticks are skipped, in which case all pending ticks are consumed at
+                    /// once.
+                    pub async fn tick(&mut self) -> u32 {
+                        let skip_missed = self.skip_missed;
+
+                        core::future::poll_fn(|cx: &mut Context<'_>| {
+                            [<$TIMERX:upper $sub_type _WAKER>].register(cx.waker());
+
+                            let ticks = &[<$TIMERX:upper $sub_type _TICKS>];
+                            if ticks.load(Ordering::Relaxed) == 0 {
+                                return Poll::Pending;
+                            }
+
+                            if skip_missed {
+                                Poll::Ready(ticks.swap(0, Ordering::Relaxed))
+                            } else {
+                                ticks.fetch_sub(1, Ordering::Relaxed);
+                                Poll::Ready(1)
+                            }
+                        })
+                        .await
+                    }
+
+                    /// Stop the timer.
+                    pub fn stop(self) -> [<Timer $sub_type>]<Uninit, PeriodicTimer> {
+                        NVIC::mask(pac::Interrupt::[<$TIMERX:upper $sub_type>]);
+                        [<$TIMERX:upper $sub_type _PERIODIC>].store(false, Ordering::Relaxed);
+                        atomic::compiler_fence(Ordering::Release);
+
+                        let timer = self.timer.disable();
+                        [<$TIMERX:upper $sub_type _WAKER>].clear();
+
+                        timer
+                    }
+                }
+
+                /// Waker of the task waiting for the timer.
+                static [<$TIMERX:upper $sub_type _WAKER>]: WakerCell = WakerCell::new();
+                /// Number of periods that have elapsed and were not yet consumed by `tick`.
+                static [<$TIMERX:upper $sub_type _TICKS>]: AtomicU32 = AtomicU32::new(0);
+                /// Whether the timer is running as a ticker.
+                static [<$TIMERX:upper $sub_type _PERIODIC>]: AtomicBool = AtomicBool::new(false);
+
+                #[interrupt]
+                #[allow(non_snake_case)]
+                fn [<$TIMERX:upper $sub_type>]() {
+                    if [<$TIMERX:upper $sub_type _PERIODIC>].load(Ordering::Relaxed) {
+                        // The timer keeps running, only the time-out interrupt is cleared.
+                        let timer = unsafe { &* cc2538_pac::$TIMERX::ptr() };
+                        timer.icr().modify(|_, w| w.[<t $sub_type:lower tocint>]().set_bit());
+                        [<$TIMERX:upper $sub_type _TICKS>].fetch_add(1, Ordering::Relaxed);
+
+                        [<$TIMERX:upper $sub_type _WAKER>].wake_by_ref();
+                    } else if [<$TIMERX:upper $sub_type _WAKER>].wake_by_ref() {
+                        NVIC::mask(pac::Interrupt::[<$TIMERX:upper $sub_type>]);
+                    }
                 }
 
                 impl [<Timer $sub_type>]<Uninit, OneShotTimer> {
-                    pub async fn wait(mut self, dur: Duration, config: &ClockConfig) -> Self {
+                    /// Wait for `dur`.
+                    ///
+                    /// Returns [`TimerError::DurationTooLong`] when the duration does not fit in
+                    /// the timer.
+                    pub async fn wait(
+                        mut self,
+                        dur: Duration,
+                        config: &ClockConfig,
+                    ) -> Result<Self, TimerError> {
                         struct Wait {
                             timer: Option<[<Timer $sub_type>]<Configured, OneShotTimer>>,
                             installed_waker: bool,
@@ -321,36 +479,23 @@ macro_rules! timer {
                             type Output = [<Timer $sub_type>]<Uninit, OneShotTimer>;
 
                             fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                                static mut WAKER: Option<Waker> = None;
-
                                 if self.timer.as_ref().unwrap().has_expired() {
                                     if self.installed_waker {
                                         NVIC::mask(pac::Interrupt::[<$TIMERX:upper $sub_type>]);
                                         atomic::compiler_fence(Ordering::Release);
                                         self.timer.as_ref().unwrap().clear_match();
-                                        drop(unsafe { WAKER.take() });
+                                        [<$TIMERX:upper $sub_type _WAKER>].clear();
                                     }
 
                                     Poll::Ready(self.timer.take().unwrap().disable())
                                 } else {
                                     if !self.installed_waker {
-                                        unsafe {
-                                            WAKER = Some(cx.waker().clone());
-                                            atomic::compiler_fence(Ordering::Release);
-                                            NVIC::unmask(pac::Interrupt::[<$TIMERX:upper $sub_type>]);
-                                        }
+                                        [<$TIMERX:upper $sub_type _WAKER>].register(cx.waker());
+                                        atomic::compiler_fence(Ordering::Release);
+                                        unsafe { NVIC::unmask(pac::Interrupt::[<$TIMERX:upper $sub_type>]) };
 
                                         self.installed_waker = true;
                                         self.timer.as_mut().unwrap().enable();
-
-                                        #[interrupt]
-                                        #[allow(non_snake_case)]
-                                        fn [<$TIMERX:upper $sub_type>]() {
-                                            if let Some(waker) = unsafe { WAKER.as_ref() } {
-                                                waker.wake_by_ref();
-                                                NVIC::mask(pac::Interrupt::[<$TIMERX:upper $sub_type>]);
-                                            }
-                                        }
                                     } else {
                                         unsafe { NVIC::unmask(pac::Interrupt::[<$TIMERX:upper $sub_type>]) };
                                     }
@@ -361,27 +506,8 @@ macro_rules! timer {
                         }
 
                         // Configure the timer
-                        let prescaler = (
-                            dur.as_nanos() /
-                            (u16::MAX as u128  * (config.io_freq() / 1_000_000) as u128)
-                        );
-                        let prescaler:u8 = prescaler.min(u8::MAX as u128) as u8;
-
-                        // XXX check if this is correct
-                        let start_value = if prescaler != 0 {(
-                            dur.as_nanos() * (config.io_freq() / 1_000_000) as u128
-                            / prescaler as u128
-                            / 1_000
-                        )} else {
-                            dur.as_nanos() * (config.io_freq() / 1_000_000) as u128
-                            / 1_000
-                        };
-
-                        let start_value:u16 = if start_value > u16::MAX as u128 {
-                            panic!("Timer delay is too big.");
-                        } else {
-                            start_value as u16
-                        };
+                        let (prescaler, start_value) =
+                            prescaler_and_start_value(dur, config.io_freq())?;
 
                         self.set_count_direction(CountDirection::Down);
                         self.set_prescaler(prescaler);
@@ -392,10 +518,10 @@ macro_rules! timer {
                         timer.clear_interrupts();
                         timer.clear_match();
 
-                        Wait {
+                        Ok(Wait {
                             timer: Some(timer),
                             installed_waker: false,
-                        }.await
+                        }.await)
                     }
                 }
 